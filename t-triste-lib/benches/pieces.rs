@@ -0,0 +1,125 @@
+use bevy::{input::InputPlugin, prelude::*, state::app::StatesPlugin};
+use criterion::{criterion_group, criterion_main, Criterion};
+use t_triste_lib::{
+    action::ActionPlugin,
+    cursor::CursorPlugin,
+    history::History,
+    piece::{
+        board::BoardCompleted, kind::PieceKind, polyomino::Polyomino, spawn_piece, Drag,
+        GridAnchor, HeldBy, PieceColor, PieceDataReadOnly, PieceId, PiecePlugin, PieceShape,
+        PieceView, TrayAnchor, SQUARE_WIDTH,
+    },
+    session::Session,
+    state::{AppState, StatePlugin},
+};
+
+/// Sprite of a piece square in the baseline, respawned every frame
+#[derive(Component)]
+struct RespawnedSquare;
+
+/// Baseline: how the pieces were drawn before their sprites were kept alive.
+/// Every square sprite is despawned before the frame and spawned again from the pieces.
+struct RespawnPlugin;
+
+impl Plugin for RespawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, clear)
+            .add_systems(Update, draw_piece);
+    }
+}
+
+fn clear(mut commands: Commands, query: Query<Entity, With<RespawnedSquare>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn draw_piece(mut commands: Commands, pieces: Query<PieceDataReadOnly>) {
+    for piece in pieces.iter() {
+        let color = piece.color();
+        for position in piece.positions() {
+            commands.spawn((
+                Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(
+                        (SQUARE_WIDTH - 1) as f32,
+                        (SQUARE_WIDTH - 1) as f32,
+                    )),
+                    ..default()
+                },
+                Transform::from_translation(position),
+                RespawnedSquare,
+            ));
+        }
+    }
+}
+
+/// Builds a headless app playing a level with the 12 pentominoes.
+/// The `respawned` app draws the pieces the way the baseline did.
+fn app(respawned: bool) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, InputPlugin, StatesPlugin))
+        .add_message::<BoardCompleted>()
+        .init_resource::<History>()
+        .init_resource::<Session>()
+        .add_plugins((ActionPlugin, StatePlugin, CursorPlugin, PiecePlugin))
+        .insert_state(AppState::Playing);
+    if respawned {
+        app.add_plugins(RespawnPlugin);
+    }
+    let mut commands = app.world_mut().commands();
+    for (index, kind) in PieceKind::PENTOMINOES.iter().enumerate() {
+        let piece = Polyomino::new(*kind, 4 * index as i32, 0);
+        if respawned {
+            // Without the sprites kept as children
+            commands.spawn((
+                PieceId(index),
+                PieceShape(piece.shape()),
+                GridAnchor(piece.anchor()),
+                TrayAnchor(piece.anchor()),
+                PieceColor(piece.color()),
+                Drag::default(),
+                HeldBy::default(),
+            ));
+        } else {
+            spawn_piece(&mut commands, index, &piece);
+        }
+    }
+    app.update();
+    app
+}
+
+fn pieces_at_rest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame with 12 pieces at rest");
+    for (name, respawned) in [("kept sprites", false), ("respawned sprites", true)] {
+        let mut app = app(respawned);
+        group.bench_function(name, |b| b.iter(|| app.update()));
+    }
+    group.finish();
+}
+
+fn piece_moving(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame with 1 of 12 pieces moving");
+    for (name, respawned) in [("kept sprites", false), ("respawned sprites", true)] {
+        let mut app = app(respawned);
+        let piece = app
+            .world_mut()
+            .query::<(Entity, &PieceId)>()
+            .iter(app.world())
+            .find(|(_, id)| id.0 == 0)
+            .map(|(entity, _)| entity)
+            .unwrap();
+        let mut column = 0;
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                column = (column + 1) % 10;
+                app.world_mut().get_mut::<GridAnchor>(piece).unwrap().0 = IVec2::new(column, 10);
+                app.update();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pieces_at_rest, piece_moving);
+criterion_main!(benches);
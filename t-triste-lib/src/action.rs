@@ -0,0 +1,584 @@
+use std::collections::HashSet;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    input::{
+        gamepad::Gamepad,
+        gamepad::GamepadButton,
+        mouse::{MouseScrollUnit, MouseWheel},
+        InputSystems,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Bindings file the player can edit, loaded at start and reloaded when it changes
+/// with the `hot_reload` feature
+pub const BINDINGS: &str = "controls.bindings.ron";
+
+/// Distance in pixels a touchpad has to scroll to count as one step of a mouse wheel
+const PIXELS_PER_WHEEL_STEP: f32 = 50.;
+
+/// Plugin that turns the mouse, keyboard and gamepad inputs into `Actions`
+/// according to the `Bindings`
+pub struct ActionPlugin;
+
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bindings>()
+            .init_resource::<Actions>()
+            .add_message::<MouseWheel>()
+            .add_systems(
+                PreUpdate,
+                update_actions.in_set(ActionSystems).after(InputSystems),
+            );
+    }
+}
+
+/// Plugin that loads the `Bindings` from the `BINDINGS` file
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Bindings>()
+            .register_asset_loader(BindingsLoader)
+            .add_systems(Startup, load_bindings)
+            .add_systems(Update, apply_bindings);
+    }
+}
+
+/// System set updating the `Actions`, in `PreUpdate`
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionSystems;
+
+/// What the player can do
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Picks up a piece
+    Pick,
+    /// Drops the moving piece where it is
+    Drop,
+    /// Turns a piece to the right
+    RotateCw,
+    /// Turns a piece to the left
+    RotateCcw,
+    /// Mirrors a piece left to right
+    Flip,
+    /// Mirrors a piece upside down
+    FlipVertical,
+    /// Reverts the last action on the pieces
+    Undo,
+    /// Applies the last undone action again
+    Redo,
+    /// Shows where a piece goes
+    Hint,
+    /// Selects the next piece
+    SelectNext,
+    /// Selects the previous piece
+    SelectPrevious,
+    /// Moves the selected piece one cell up
+    MoveUp,
+    /// Moves the selected piece one cell down
+    MoveDown,
+    /// Moves the selected piece one cell left
+    MoveLeft,
+    /// Moves the selected piece one cell right
+    MoveRight,
+    /// Puts the selected piece back where it started
+    ReturnToTray,
+    /// Pauses or resumes the game
+    Pause,
+    /// Highlights the previous choice of a menu
+    MenuUp,
+    /// Highlights the next choice of a menu
+    MenuDown,
+    /// Accepts the highlighted choice of a menu
+    Confirm,
+    /// Goes back to the previous menu
+    Back,
+    /// Leaves the paused level
+    Quit,
+}
+
+/// A button of a device
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+    /// One step of the mouse wheel
+    Wheel(WheelDirection),
+}
+
+/// Direction the mouse wheel is scrolled to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WheelDirection {
+    Up,
+    Down,
+}
+
+/// Kind of device an action comes from, which tells the piece it applies to:
+/// the piece under the mouse cursor, the piece selected with the keyboard,
+/// or the piece under the virtual cursor of the gamepads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Device {
+    Mouse,
+    Keyboard,
+    Gamepad,
+}
+
+impl Input {
+    /// Returns the kind of device the button belongs to
+    pub fn device(&self) -> Device {
+        match self {
+            Input::Key(_) => Device::Keyboard,
+            Input::Mouse(_) | Input::Wheel(_) => Device::Mouse,
+            Input::Gamepad(_) => Device::Gamepad,
+        }
+    }
+}
+
+/// A button triggering an action
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub action: Action,
+    pub input: Input,
+    /// Whether Shift has to be held
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shift: bool,
+    /// Whether Control has to be held
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ctrl: bool,
+    /// Whether the action happens when the button is released rather than pressed
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub on_release: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl Binding {
+    pub fn new(action: Action, input: Input) -> Self {
+        Binding {
+            action,
+            input,
+            shift: false,
+            ctrl: false,
+            on_release: false,
+        }
+    }
+
+    /// Requires Shift to be held
+    pub fn with_shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    /// Requires Control to be held
+    pub fn with_ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    /// Triggers the action when the button is released
+    pub fn on_release(mut self) -> Self {
+        self.on_release = true;
+        self
+    }
+
+    fn nb_modifiers(&self) -> usize {
+        usize::from(self.shift) + usize::from(self.ctrl)
+    }
+}
+
+/// Resource mapping the buttons to the actions.
+///
+/// Bindings are written in RON:
+/// ```text
+/// (
+///     bindings: [
+///         (action: Pick, input: Mouse(Left)),
+///         (action: Drop, input: Mouse(Left), on_release: true),
+///         (action: Undo, input: Key(KeyZ), ctrl: true),
+///         (action: Flip, input: Gamepad(North)),
+///     ],
+/// )
+/// ```
+#[derive(Asset, Resource, TypePath, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bindings {
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Action::*;
+        use Input::{Gamepad as Pad, Key, Mouse, Wheel};
+        Bindings {
+            bindings: vec![
+                Binding::new(Pick, Mouse(MouseButton::Left)),
+                Binding::new(Drop, Mouse(MouseButton::Left)).on_release(),
+                Binding::new(RotateCw, Mouse(MouseButton::Right)),
+                Binding::new(RotateCcw, Mouse(MouseButton::Right)).with_shift(),
+                Binding::new(Flip, Mouse(MouseButton::Middle)),
+                Binding::new(FlipVertical, Mouse(MouseButton::Middle)).with_shift(),
+                Binding::new(RotateCcw, Wheel(WheelDirection::Up)),
+                Binding::new(RotateCw, Wheel(WheelDirection::Down)),
+                Binding::new(SelectNext, Key(KeyCode::Tab)),
+                Binding::new(SelectPrevious, Key(KeyCode::Tab)).with_shift(),
+                Binding::new(MoveUp, Key(KeyCode::ArrowUp)),
+                Binding::new(MoveDown, Key(KeyCode::ArrowDown)),
+                Binding::new(MoveLeft, Key(KeyCode::ArrowLeft)),
+                Binding::new(MoveRight, Key(KeyCode::ArrowRight)),
+                Binding::new(RotateCw, Key(KeyCode::KeyR)),
+                Binding::new(RotateCcw, Key(KeyCode::KeyE)),
+                Binding::new(Flip, Key(KeyCode::KeyF)),
+                Binding::new(FlipVertical, Key(KeyCode::KeyF)).with_shift(),
+                Binding::new(Drop, Key(KeyCode::Enter)),
+                Binding::new(ReturnToTray, Key(KeyCode::Backspace)),
+                Binding::new(Undo, Key(KeyCode::KeyZ)).with_ctrl(),
+                Binding::new(Redo, Key(KeyCode::KeyY)).with_ctrl(),
+                Binding::new(Redo, Key(KeyCode::KeyZ))
+                    .with_ctrl()
+                    .with_shift(),
+                Binding::new(Hint, Key(KeyCode::KeyH)),
+                Binding::new(Pause, Key(KeyCode::Escape)),
+                Binding::new(Pick, Pad(GamepadButton::South)),
+                Binding::new(Drop, Pad(GamepadButton::South)),
+                Binding::new(RotateCw, Pad(GamepadButton::West)),
+                Binding::new(RotateCcw, Pad(GamepadButton::East)),
+                Binding::new(Flip, Pad(GamepadButton::North)),
+                Binding::new(SelectNext, Pad(GamepadButton::RightTrigger)),
+                Binding::new(SelectPrevious, Pad(GamepadButton::LeftTrigger)),
+                Binding::new(Undo, Pad(GamepadButton::Select)),
+                Binding::new(Pause, Pad(GamepadButton::Start)),
+                Binding::new(MenuUp, Key(KeyCode::ArrowUp)),
+                Binding::new(MenuDown, Key(KeyCode::ArrowDown)),
+                Binding::new(Confirm, Key(KeyCode::Enter)),
+                Binding::new(Back, Key(KeyCode::Escape)),
+                Binding::new(Quit, Key(KeyCode::KeyQ)),
+                Binding::new(MenuUp, Pad(GamepadButton::DPadUp)),
+                Binding::new(MenuDown, Pad(GamepadButton::DPadDown)),
+                Binding::new(Confirm, Pad(GamepadButton::South)),
+                Binding::new(Back, Pad(GamepadButton::East)),
+                Binding::new(Quit, Pad(GamepadButton::Select)),
+            ],
+        }
+    }
+}
+
+impl Bindings {
+    /// Parses bindings from their RON representation
+    pub fn from_ron(bindings: &str) -> Result<Self, BindingsError> {
+        Ok(ron::de::from_str(bindings)?)
+    }
+
+    /// Returns the actions triggered by the buttons pressed, or released, this frame.
+    ///
+    /// A binding matches when its modifiers are held. When several bindings of a button
+    /// match, only the ones requiring the most modifiers are kept, so that Shift+Tab
+    /// does not trigger the action bound to Tab.
+    pub fn actions(
+        &self,
+        triggered: &[(Input, bool)],
+        shift: bool,
+        ctrl: bool,
+    ) -> Vec<(Device, Action)> {
+        let mut actions = Vec::new();
+        for (input, on_release) in triggered {
+            let matching: Vec<&Binding> = self
+                .bindings
+                .iter()
+                .filter(|binding| binding.input == *input && binding.on_release == *on_release)
+                .filter(|binding| (!binding.shift || shift) && (!binding.ctrl || ctrl))
+                .collect();
+            let Some(most_modifiers) = matching.iter().map(|binding| binding.nb_modifiers()).max()
+            else {
+                continue;
+            };
+            actions.extend(
+                matching
+                    .into_iter()
+                    .filter(|binding| binding.nb_modifiers() == most_modifiers)
+                    .map(|binding| (input.device(), binding.action)),
+            );
+        }
+        actions
+    }
+}
+
+/// Resource holding the actions triggered this frame, with the device they come from
+#[derive(Resource, Debug, Default)]
+pub struct Actions(HashSet<(Device, Action)>);
+
+impl Actions {
+    /// Returns true if the action was triggered this frame by any device
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.0.iter().any(|(_, triggered)| *triggered == action)
+    }
+
+    /// Returns true if the action was triggered this frame by a kind of device
+    pub fn just_pressed_by(&self, device: Device, action: Action) -> bool {
+        self.0.contains(&(device, action))
+    }
+
+    /// Triggers an action for this frame
+    pub fn press(&mut self, device: Device, action: Action) {
+        self.0.insert((device, action));
+    }
+
+    /// Forgets the actions of the previous frame
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Reasons why the bindings cannot be loaded
+#[derive(Debug, Error)]
+pub enum BindingsError {
+    #[error("could not read the bindings file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the bindings file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+/// Loads `.bindings.ron` files into `Bindings` assets
+#[derive(Default)]
+struct BindingsLoader;
+
+impl AssetLoader for BindingsLoader {
+    type Asset = Bindings;
+    type Settings = ();
+    type Error = BindingsError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Bindings, BindingsError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Bindings::from_ron(&String::from_utf8_lossy(&bytes))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bindings.ron"]
+    }
+}
+
+/// Resource holding the bindings file
+#[derive(Resource)]
+struct BindingsHandle(Handle<Bindings>);
+
+// Systems
+fn load_bindings(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BindingsHandle(asset_server.load(BINDINGS)));
+}
+
+/// Replaces the default bindings once the file is loaded, and whenever it changes on disk
+/// with the `hot_reload` feature
+fn apply_bindings(
+    mut asset_events: MessageReader<AssetEvent<Bindings>>,
+    handle: Res<BindingsHandle>,
+    assets: Res<Assets<Bindings>>,
+    mut bindings: ResMut<Bindings>,
+) {
+    let changed = asset_events
+        .read()
+        .filter(|event| {
+            event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0)
+        })
+        .count();
+    if changed == 0 {
+        return;
+    }
+    if let Some(loaded) = assets.get(&handle.0) {
+        bindings.set_if_neq(loaded.clone());
+    }
+}
+
+fn update_actions(
+    bindings: Res<Bindings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mut mouse_wheel: MessageReader<MouseWheel>,
+    gamepads: Query<&Gamepad>,
+    mut scrolled: Local<f32>,
+    mut actions: ResMut<Actions>,
+) {
+    actions.clear();
+    // The wheel triggers at most one step per frame. The other whole steps are dropped
+    // so that the piece stops turning with the wheel. Only a fraction of a step is kept.
+    for event in mouse_wheel.read() {
+        *scrolled += match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_WHEEL_STEP,
+        };
+    }
+    let wheel = if *scrolled >= 1. {
+        Some(WheelDirection::Up)
+    } else if *scrolled <= -1. {
+        Some(WheelDirection::Down)
+    } else {
+        None
+    };
+    *scrolled = scrolled.fract();
+    let pressed = keyboard_input
+        .get_just_pressed()
+        .map(|key| (Input::Key(*key), false))
+        .chain(
+            mouse_button_input
+                .get_just_pressed()
+                .map(|button| (Input::Mouse(*button), false)),
+        )
+        .chain(gamepads.iter().flat_map(|gamepad| {
+            gamepad
+                .get_just_pressed()
+                .map(|button| (Input::Gamepad(*button), false))
+        }));
+    let released = keyboard_input
+        .get_just_released()
+        .map(|key| (Input::Key(*key), true))
+        .chain(
+            mouse_button_input
+                .get_just_released()
+                .map(|button| (Input::Mouse(*button), true)),
+        )
+        .chain(gamepads.iter().flat_map(|gamepad| {
+            gamepad
+                .get_just_released()
+                .map(|button| (Input::Gamepad(*button), true))
+        }));
+    let triggered: Vec<(Input, bool)> = pressed
+        .chain(released)
+        .chain(wheel.map(|direction| (Input::Wheel(direction), false)))
+        .collect();
+    if triggered.is_empty() {
+        return;
+    }
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    for (device, action) in bindings.actions(&triggered, shift, ctrl) {
+        actions.press(device, action);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings_file_is_the_default() {
+        // Given
+        let file = include_str!("../../t-triste/assets/controls.bindings.ron");
+
+        // When
+        let bindings = Bindings::from_ron(file).unwrap();
+
+        // Then
+        assert_eq!(bindings, Bindings::default());
+    }
+
+    #[test]
+    fn test_most_modifiers_win() {
+        // Given
+        let bindings = Bindings::default();
+        let z = [(Input::Key(KeyCode::KeyZ), false)];
+        let right_click = [(Input::Mouse(MouseButton::Right), false)];
+        let release = [(Input::Mouse(MouseButton::Left), true)];
+
+        // Then
+        assert_eq!(bindings.actions(&z, false, false), vec![]);
+        assert_eq!(
+            bindings.actions(&z, false, true),
+            vec![(Device::Keyboard, Action::Undo)]
+        );
+        assert_eq!(
+            bindings.actions(&z, true, true),
+            vec![(Device::Keyboard, Action::Redo)]
+        );
+        assert_eq!(
+            bindings.actions(&right_click, true, false),
+            vec![(Device::Mouse, Action::RotateCcw)]
+        );
+        // A modifier held while dragging does not prevent the drop
+        assert_eq!(
+            bindings.actions(&release, true, true),
+            vec![(Device::Mouse, Action::Drop)]
+        );
+        assert_eq!(
+            bindings.actions(
+                &[(Input::Gamepad(GamepadButton::South), false)],
+                false,
+                false
+            ),
+            vec![
+                (Device::Gamepad, Action::Pick),
+                (Device::Gamepad, Action::Drop),
+                (Device::Gamepad, Action::Confirm)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wheel_steps() {
+        // Given
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .add_plugins(ActionPlugin);
+        let window = app.world_mut().spawn_empty().id();
+        let wheel = |unit, y| MouseWheel {
+            unit,
+            x: 0.,
+            y,
+            window,
+        };
+
+        // When
+        app.world_mut()
+            .write_message(wheel(MouseScrollUnit::Pixel, 2. * PIXELS_PER_WHEEL_STEP));
+        app.update();
+
+        // Then
+        let actions = app.world().resource::<Actions>();
+        assert!(actions.just_pressed_by(Device::Mouse, Action::RotateCcw));
+
+        // When
+        app.update();
+
+        // Then
+        // The second step up is dropped
+        let actions = app.world().resource::<Actions>();
+        assert!(!actions.just_pressed(Action::RotateCcw));
+
+        // When
+        app.world_mut()
+            .write_message(wheel(MouseScrollUnit::Pixel, -0.6 * PIXELS_PER_WHEEL_STEP));
+        app.update();
+
+        // Then
+        let actions = app.world().resource::<Actions>();
+        assert!(!actions.just_pressed(Action::RotateCw));
+
+        // When
+        app.world_mut()
+            .write_message(wheel(MouseScrollUnit::Pixel, -0.6 * PIXELS_PER_WHEEL_STEP));
+        app.update();
+
+        // Then
+        let actions = app.world().resource::<Actions>();
+        assert!(actions.just_pressed_by(Device::Mouse, Action::RotateCw));
+    }
+
+    #[test]
+    fn test_invalid_bindings() {
+        assert!(matches!(
+            Bindings::from_ron("(bindings: [(action: Jump, input: Key(Space))])"),
+            Err(BindingsError::Ron(_))
+        ));
+    }
+}
@@ -1,6 +1,14 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU};
+
+use bevy::input::touch::Touches;
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::action::{Action, ActionSystems, Actions, Device};
 
-/// Plugin that handles cursor tracking and mouse button state
+/// Plugin that handles cursor tracking and the pick and drop state of the mouse.
+/// On touchscreens, the first finger acts as the mouse: touching picks the piece under it
+/// and lifting the finger drops it. A double tap or a two-finger twist rotates the piece.
 pub struct CursorPlugin;
 
 impl Plugin for CursorPlugin {
@@ -9,37 +17,388 @@ impl Plugin for CursorPlugin {
             current_pos: Vec2::default(),
             last_click_pos: Vec2::default(),
             is_pressed: false,
+            is_dragging: false,
         })
-        .add_systems(PreUpdate, cursor_state);
+        .add_systems(
+            PreUpdate,
+            (cursor_position, touch_gestures, cursor_buttons)
+                .chain()
+                .after(ActionSystems),
+        );
     }
 }
 
+/// Distance in world coordinates the cursor has to move, once a piece is picked,
+/// before a piece is dragged. A simple click does not nudge the piece.
+pub const DRAG_THRESHOLD: f32 = 5.;
+
+/// Longest time in seconds between two taps of a double tap, and for a touch to be a tap
+pub const DOUBLE_TAP_DELAY: f32 = 0.3;
+
+/// Farthest distance in logical pixels between two taps of a double tap,
+/// and for a touch to be a tap
+pub const TAP_DISTANCE: f32 = 20.;
+
 /// Resource that tracks the current cursor state
 #[derive(Resource)]
 pub struct Cursor {
-    /// The current position of the cursor in screen coordinates
+    /// The current position of the cursor in world coordinates
     pub current_pos: Vec2,
-    /// The position where the left mouse button was last pressed
+    /// The position in world coordinates where the mouse last picked, over a piece or not
     pub last_click_pos: Vec2,
-    /// Whether the left mouse button is currently pressed
+    /// Whether the mouse picked, over a piece or not, and did not drop yet
     pub is_pressed: bool,
+    /// Whether the cursor moved further than `DRAG_THRESHOLD` since the mouse picked
+    pub is_dragging: bool,
+}
+
+/// Converts a position in the primary window into world coordinates through the active camera
+fn screen_to_world(position: Vec2, cameras: &Query<(&Camera, &GlobalTransform)>) -> Option<Vec2> {
+    let (camera, camera_transform) = cameras.iter().find(|(camera, _)| camera.is_active)?;
+    camera.viewport_to_world_2d(camera_transform, position).ok()
 }
 
-fn cursor_state(
-    mut cursor_moved_event: MessageReader<CursorMoved>,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
+/// Progress of the touch gestures
+#[derive(Default)]
+struct TouchGesture {
+    /// Finger acting as the mouse: the one that touched the screen when no other did
+    primary: Option<u64>,
+    /// When the primary finger touched the screen
+    pressed_at: f32,
+    /// Whether the primary finger is doing a double tap, which rotates the piece instead
+    /// of picking it
+    double_tap: bool,
+    /// When and where the last tap ended
+    last_tap: Option<(f32, Vec2)>,
+    /// Angle between the first two fingers at the previous frame
+    twist_angle: Option<f32>,
+    /// Angle the fingers turned since the last quarter turn
+    twist: f32,
+}
+
+// Systems
+fn cursor_position(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
     mut cursor: ResMut<Cursor>,
 ) {
-    for event in cursor_moved_event.read() {
-        cursor.current_pos = event.position;
+    // The world position is computed every frame, as the camera may move while the
+    // cursor stands still.
+    if let Some(position) = windows
+        .single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .and_then(|position| screen_to_world(position, &cameras))
+    {
+        cursor.current_pos = position;
+    }
+}
+
+fn touch_gestures(
+    time: Res<Time>,
+    touches: Res<Touches>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut gesture: Local<TouchGesture>,
+    mut actions: ResMut<Actions>,
+    mut cursor: ResMut<Cursor>,
+) {
+    let now = time.elapsed_secs();
+    for touch in touches.iter_just_pressed() {
+        if gesture.primary.is_some() {
+            continue;
+        }
+        gesture.primary = Some(touch.id());
+        gesture.pressed_at = now;
+        gesture.double_tap = gesture.last_tap.take().is_some_and(|(tapped_at, tap)| {
+            now - tapped_at <= DOUBLE_TAP_DELAY && tap.distance(touch.position()) <= TAP_DISTANCE
+        });
+        if let Some(position) = screen_to_world(touch.position(), &cameras) {
+            cursor.current_pos = position;
+        }
+        let action = if gesture.double_tap {
+            Action::RotateCw
+        } else {
+            Action::Pick
+        };
+        actions.press(Device::Mouse, action);
+    }
 
-        if mouse_button_input.just_pressed(MouseButton::Left) {
-            cursor.last_click_pos = event.position;
-            cursor.is_pressed = true;
+    // The cursor follows the primary finger until it is lifted
+    if let Some(id) = gesture.primary {
+        let lifted = touches
+            .get_released(id)
+            .or_else(|| touches.iter_just_canceled().find(|touch| touch.id() == id));
+        let touch = touches.get_pressed(id).or(lifted);
+        if let Some(position) = touch.and_then(|touch| screen_to_world(touch.position(), &cameras))
+        {
+            cursor.current_pos = position;
         }
+        if touch.is_none() || lifted.is_some() {
+            gesture.primary = None;
+            if !gesture.double_tap {
+                actions.press(Device::Mouse, Action::Drop);
+                gesture.last_tap = lifted
+                    .filter(|touch| {
+                        now - gesture.pressed_at <= DOUBLE_TAP_DELAY
+                            && touch.distance().length() <= TAP_DISTANCE
+                    })
+                    .map(|touch| (now, touch.position()));
+            }
+        }
+    }
 
-        if mouse_button_input.just_released(MouseButton::Left) {
-            cursor.is_pressed = false;
+    // Twisting two fingers turns the piece a quarter every time they turn more than
+    // an eighth of a turn
+    let mut fingers: Vec<_> = touches.iter().collect();
+    fingers.sort_by_key(|touch| touch.id());
+    let [first, second, ..] = fingers[..] else {
+        gesture.twist_angle = None;
+        gesture.twist = 0.;
+        return;
+    };
+    // The window y axis points down, so the angle is measured clockwise
+    let direction = second.position() - first.position();
+    let angle = (-direction.y).atan2(direction.x);
+    if let Some(previous) = gesture.twist_angle {
+        gesture.twist += (angle - previous + PI).rem_euclid(TAU) - PI;
+        if gesture.twist >= FRAC_PI_4 {
+            actions.press(Device::Mouse, Action::RotateCcw);
+            gesture.twist -= FRAC_PI_2;
+        } else if gesture.twist <= -FRAC_PI_4 {
+            actions.press(Device::Mouse, Action::RotateCw);
+            gesture.twist += FRAC_PI_2;
         }
     }
+    gesture.twist_angle = Some(angle);
+}
+
+fn cursor_buttons(actions: Res<Actions>, mut cursor: ResMut<Cursor>) {
+    if actions.just_pressed_by(Device::Mouse, Action::Pick) {
+        cursor.last_click_pos = cursor.current_pos;
+        cursor.is_pressed = true;
+    }
+
+    if cursor.is_pressed && cursor.current_pos.distance(cursor.last_click_pos) > DRAG_THRESHOLD {
+        cursor.is_dragging = true;
+    }
+
+    if actions.just_pressed_by(Device::Mouse, Action::Drop) {
+        cursor.is_pressed = false;
+        cursor.is_dragging = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::camera::{CameraProjection, ComputedCameraValues, RenderTargetInfo};
+    use bevy::input::{
+        mouse::MouseButtonInput,
+        touch::{TouchInput, TouchPhase},
+        ButtonState, InputPlugin,
+    };
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::action::ActionPlugin;
+
+    /// Builds an app with an 800x600 window and a camera zoomed out twice,
+    /// centered on `(-50, 30)`
+    fn app(cursor_position: Option<Vec2>) -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, ActionPlugin, CursorPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                50,
+            )));
+        let mut window = Window {
+            resolution: (800, 600).into(),
+            ..default()
+        };
+        window.set_cursor_position(cursor_position);
+        let window = app.world_mut().spawn((window, PrimaryWindow)).id();
+        let mut projection = OrthographicProjection {
+            scale: 2.,
+            ..OrthographicProjection::default_2d()
+        };
+        projection.update(800., 600.);
+        app.world_mut().spawn((
+            Camera {
+                computed: ComputedCameraValues {
+                    clip_from_view: projection.get_clip_from_view(),
+                    target_info: Some(RenderTargetInfo {
+                        physical_size: UVec2::new(800, 600),
+                        scale_factor: 1.,
+                    }),
+                    ..default()
+                },
+                ..default()
+            },
+            GlobalTransform::from_translation(Vec3::new(-50., 30., 0.)),
+        ));
+        (app, window)
+    }
+
+    fn touch(app: &mut App, window: Entity, id: u64, phase: TouchPhase, position: Vec2) {
+        app.world_mut().write_message(TouchInput {
+            phase,
+            position,
+            window,
+            force: None,
+            id,
+        });
+    }
+
+    fn just_pressed(app: &App, action: Action) -> bool {
+        app.world()
+            .resource::<Actions>()
+            .just_pressed_by(Device::Mouse, action)
+    }
+
+    #[test]
+    fn test_cursor_is_in_world_coordinates() {
+        // Given
+        // 100 pixels right and 100 pixels up from the centre of the window
+        let (mut app, window) = app(Some(Vec2::new(500., 200.)));
+
+        // When
+        app.world_mut().write_message(MouseButtonInput {
+            button: MouseButton::Left,
+            state: ButtonState::Pressed,
+            window,
+        });
+        app.update();
+
+        // Then
+        let cursor = app.world().resource::<Cursor>();
+        assert!(cursor.current_pos.abs_diff_eq(Vec2::new(150., 230.), 1e-3));
+        assert_eq!(cursor.last_click_pos, cursor.current_pos);
+        assert!(cursor.is_pressed);
+        assert!(!cursor.is_dragging);
+
+        // When
+        let mut windows = app.world_mut().query::<&mut Window>();
+        let mut window = windows.single_mut(app.world_mut()).unwrap();
+        window.set_cursor_position(Some(Vec2::new(502., 200.)));
+        app.update();
+
+        // Then
+        // Moving less than the threshold is not a drag
+        assert!(!app.world().resource::<Cursor>().is_dragging);
+
+        // When
+        let mut window = windows.single_mut(app.world_mut()).unwrap();
+        window.set_cursor_position(Some(Vec2::new(510., 200.)));
+        app.update();
+
+        // Then
+        assert!(app.world().resource::<Cursor>().is_dragging);
+    }
+
+    #[test]
+    fn test_touch_drags_like_the_mouse() {
+        // Given
+        let (mut app, window) = app(None);
+
+        // When
+        touch(
+            &mut app,
+            window,
+            0,
+            TouchPhase::Started,
+            Vec2::new(500., 200.),
+        );
+        app.update();
+
+        // Then
+        assert!(just_pressed(&app, Action::Pick));
+        let cursor = app.world().resource::<Cursor>();
+        assert!(cursor.current_pos.abs_diff_eq(Vec2::new(150., 230.), 1e-3));
+        assert!(cursor.is_pressed);
+
+        // When
+        touch(
+            &mut app,
+            window,
+            0,
+            TouchPhase::Moved,
+            Vec2::new(530., 200.),
+        );
+        app.update();
+
+        // Then
+        let cursor = app.world().resource::<Cursor>();
+        assert!(cursor.current_pos.abs_diff_eq(Vec2::new(210., 230.), 1e-3));
+        assert!(cursor.is_dragging);
+
+        // When
+        touch(
+            &mut app,
+            window,
+            0,
+            TouchPhase::Ended,
+            Vec2::new(530., 200.),
+        );
+        app.update();
+
+        // Then
+        assert!(just_pressed(&app, Action::Drop));
+        assert!(!app.world().resource::<Cursor>().is_pressed);
+    }
+
+    #[test]
+    fn test_touch_gestures_rotate() {
+        // Given
+        let (mut app, window) = app(None);
+        let position = Vec2::new(400., 300.);
+
+        // When
+        touch(&mut app, window, 0, TouchPhase::Started, position);
+        app.update();
+        touch(&mut app, window, 0, TouchPhase::Ended, position);
+        app.update();
+        touch(&mut app, window, 1, TouchPhase::Started, position + Vec2::X);
+        app.update();
+
+        // Then
+        // The second tap rotates the piece instead of picking it
+        assert!(just_pressed(&app, Action::RotateCw));
+        assert!(!just_pressed(&app, Action::Pick));
+
+        // When
+        touch(&mut app, window, 1, TouchPhase::Ended, position + Vec2::X);
+        app.update();
+
+        // Then
+        assert!(!just_pressed(&app, Action::Drop));
+
+        // When
+        touch(
+            &mut app,
+            window,
+            2,
+            TouchPhase::Started,
+            Vec2::new(300., 300.),
+        );
+        touch(
+            &mut app,
+            window,
+            3,
+            TouchPhase::Started,
+            Vec2::new(400., 300.),
+        );
+        app.update();
+        // The second finger turns a quarter counter-clockwise around the first one
+        touch(
+            &mut app,
+            window,
+            3,
+            TouchPhase::Moved,
+            Vec2::new(300., 200.),
+        );
+        app.update();
+
+        // Then
+        assert!(just_pressed(&app, Action::RotateCcw));
+    }
 }
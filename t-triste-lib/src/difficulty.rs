@@ -0,0 +1,133 @@
+use crate::piece::{board::Board, orientations};
+use crate::solver::{SearchStats, Solver, SolverPiece};
+
+/// Solutions counted before the search stops: past this point, more solutions
+/// do not make a puzzle noticeably easier
+const SOLUTION_LIMIT: usize = 100;
+
+/// Lowest difficulty score
+pub const MIN_DIFFICULTY: u8 = 1;
+
+/// Highest difficulty score
+pub const MAX_DIFFICULTY: u8 = 10;
+
+/// Metrics telling how hard it is to fill a board with a set of pieces
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// Number of pieces to place
+    pub nb_pieces: usize,
+    /// Figures of the solver run, stopped after `SOLUTION_LIMIT` solutions
+    pub stats: SearchStats,
+    /// Average number of distinct orientations of the pieces, from 1 to 8
+    pub orientations: f32,
+}
+
+impl Analysis {
+    /// Returns the difficulty score, from `MIN_DIFFICULTY` to `MAX_DIFFICULTY`,
+    /// or None when the puzzle has no solution
+    pub fn score(&self) -> Option<u8> {
+        if self.stats.solutions == 0 {
+            return None;
+        }
+        let nodes = self.stats.nodes.max(1) as f32;
+        let solutions = self.stats.solutions as f32;
+        // A solver going straight to a solution tries one placement per piece:
+        // the extra placements are the wrong tracks a player may follow too
+        let search = (nodes / solutions / self.nb_pieces.max(1) as f32)
+            .log2()
+            .max(0.);
+        // Forced moves leave no choice, so they are easy to find
+        let forced = self.stats.forced_moves as f32 / nodes;
+        // Every other solution is another way to succeed
+        let solutions = solutions.log2();
+        // Pieces with many orientations are harder to picture in place
+        let orientations = (self.orientations - 1.) / 7.;
+
+        let raw = 1. + search + 2. * orientations - 2. * forced - 0.25 * solutions;
+        Some(
+            raw.round()
+                .clamp(MIN_DIFFICULTY as f32, MAX_DIFFICULTY as f32) as u8,
+        )
+    }
+}
+
+/// Analyzes a puzzle, the pieces being moved freely by the player
+pub fn analyze(board: &Board, pieces: &[SolverPiece]) -> Analysis {
+    let orientations = pieces
+        .iter()
+        .map(|piece| orientations(&piece.shape, piece.allowed).len() as f32)
+        .sum::<f32>()
+        / pieces.len().max(1) as f32;
+    Analysis {
+        nb_pieces: pieces.len(),
+        stats: Solver::new(board, pieces).stats(Some(SOLUTION_LIMIT)),
+        orientations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::{kind::PieceKind, AllowedTransforms, PieceBuilder};
+
+    fn pieces(kinds: &[PieceKind]) -> Vec<SolverPiece> {
+        kinds
+            .iter()
+            .map(|kind| SolverPiece::new(kind.shape(), AllowedTransforms::RotationsAndFlips))
+            .collect()
+    }
+
+    #[test]
+    fn test_forced_puzzle_is_the_easiest() {
+        // Given
+        // X X X X
+        // X X X X
+        let board = Board::new(0, 0, 2, 4);
+        let pieces = pieces(&[PieceKind::TetrominoI, PieceKind::TetrominoI]);
+
+        // When
+        let analysis = analyze(&board, &pieces);
+
+        // Then
+        assert_eq!(analysis.orientations, 2.);
+        assert_eq!(analysis.score(), Some(MIN_DIFFICULTY));
+    }
+
+    #[test]
+    fn test_difficulty_grows_with_the_search() {
+        // Given
+        let first_steps = analyze(
+            &Board::new(6, 5, 3, 5),
+            &["X\nX\nX", "X.\nX.\nXX", ".XX\nXX.", "X.\nXX", "X"]
+                .iter()
+                .map(|pattern| {
+                    SolverPiece::new(
+                        PieceBuilder::from_pattern(pattern).unwrap(),
+                        AllowedTransforms::RotationsAndFlips,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
+        let pentominoes = analyze(&Board::new(0, 0, 3, 20), &pieces(&PieceKind::PENTOMINOES));
+
+        // Then
+        let easy = first_steps.score().unwrap();
+        let hard = pentominoes.score().unwrap();
+        assert!(
+            easy < hard,
+            "{first_steps:?} is not easier than {pentominoes:?}"
+        );
+        assert!(hard <= MAX_DIFFICULTY);
+        assert_eq!(pentominoes.stats.solutions, 8);
+    }
+
+    #[test]
+    fn test_unsolvable_puzzle_has_no_score() {
+        // Given
+        let board = Board::new(0, 0, 4, 5);
+        let pieces = pieces(&PieceKind::TETROMINOES);
+
+        // Then
+        assert_eq!(analyze(&board, &pieces).score(), None);
+    }
+}
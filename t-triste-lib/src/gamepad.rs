@@ -0,0 +1,274 @@
+use bevy::input::gamepad::Gamepad;
+use bevy::prelude::*;
+use bevy::sprite::Sprite;
+
+use crate::action::{Action, Actions, Device};
+use crate::cursor::Cursor;
+use crate::history::History;
+use crate::keyboard::{cycle, Selection};
+use crate::piece::{
+    board::Board, can_handle, flip_piece, pick_piece, rotate_piece, FlipAxis, Piece, PieceData,
+    PieceDataItem, PieceView, Rotation,
+};
+use crate::place_piece;
+use crate::session::Session;
+use crate::state::{AppState, InGame};
+
+/// Speed of the virtual cursor when the stick is fully tilted, in world units per second
+const POINTER_SPEED: f32 = 500.;
+
+/// Plugin that lets the player handle the pieces with a gamepad.
+/// The left stick moves a virtual cursor and, by default, South picks and drops the piece
+/// under it, West and East rotate it, North flips it and the shoulder buttons cycle the pieces.
+///
+/// Gamepads are only detected when the `gamepad` feature is enabled.
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GamepadCursor>()
+            .add_systems(OnEnter(InGame), spawn_pointer)
+            .add_systems(
+                Update,
+                (
+                    reset_gamepad_cursor.run_if(resource_added::<Board>),
+                    (move_pointer, gamepad_piece).run_if(in_state(AppState::Playing)),
+                    draw_pointer,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Resource that tracks the virtual cursor driven by the gamepads
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Default)]
+pub struct GamepadCursor {
+    /// The position of the virtual cursor in world coordinates
+    pub position: Vec2,
+    /// The position of the virtual cursor when the held piece was picked
+    pub grab_position: Vec2,
+    /// `PieceId` of the piece held with the gamepad
+    pub held: Option<usize>,
+}
+
+impl GamepadCursor {
+    /// Returns the virtual cursor as a mouse cursor, for the pieces to follow it
+    pub fn as_cursor(&self) -> Cursor {
+        Cursor {
+            current_pos: self.position,
+            last_click_pos: self.grab_position,
+            is_pressed: self.held.is_some(),
+            is_dragging: self.held.is_some(),
+        }
+    }
+}
+
+/// Marker component for the sprite of the virtual cursor
+#[derive(Component)]
+struct GamepadPointer;
+
+/// Returns the area of the world seen by the active camera
+fn visible_area(cameras: &Query<(&Camera, &GlobalTransform)>) -> Option<Rect> {
+    let (camera, camera_transform) = cameras.iter().find(|(camera, _)| camera.is_active)?;
+    let viewport = camera.logical_viewport_rect()?;
+    let min = camera
+        .viewport_to_world_2d(camera_transform, viewport.min)
+        .ok()?;
+    let max = camera
+        .viewport_to_world_2d(camera_transform, viewport.max)
+        .ok()?;
+    Some(Rect::from_corners(min, max))
+}
+
+// Systems
+fn reset_gamepad_cursor(mut gamepad_cursor: ResMut<GamepadCursor>) {
+    *gamepad_cursor = GamepadCursor::default();
+}
+
+fn spawn_pointer(mut commands: Commands) {
+    commands.spawn((
+        Sprite {
+            color: Color::srgba(0.3, 0.2, 0.3, 0.8),
+            custom_size: Some(Vec2::splat(12.)),
+            ..default()
+        },
+        // Above the pieces
+        Transform::from_translation(Vec3::Z * 2.),
+        Visibility::Hidden,
+        GamepadPointer,
+        DespawnOnExit(InGame),
+    ));
+}
+
+fn move_pointer(
+    time: Res<Time>,
+    gamepads: Query<&Gamepad>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut gamepad_cursor: ResMut<GamepadCursor>,
+) {
+    let stick: Vec2 = gamepads.iter().map(Gamepad::left_stick).sum();
+    if stick == Vec2::ZERO {
+        return;
+    }
+    let mut position =
+        gamepad_cursor.position + stick.clamp_length_max(1.) * POINTER_SPEED * time.delta_secs();
+    if let Some(area) = visible_area(&cameras) {
+        position = position.clamp(area.min, area.max);
+    }
+    gamepad_cursor.position = position;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn gamepad_piece(
+    actions: Res<Actions>,
+    mut gamepad_cursor: ResMut<GamepadCursor>,
+    mut selection: ResMut<Selection>,
+    mut pieces: Query<PieceData>,
+    mut board: Option<ResMut<Board>>,
+    mut history: ResMut<History>,
+    mut session: ResMut<Session>,
+) {
+    let just_pressed = |action: Action| actions.just_pressed_by(Device::Gamepad, action);
+    let previous = just_pressed(Action::SelectPrevious);
+    let next = just_pressed(Action::SelectNext);
+
+    // The held piece follows the virtual cursor, and is dropped before picking another one
+    let mut dropped = false;
+    let held = gamepad_cursor
+        .held
+        .and_then(|id| pieces.iter_mut().find(|piece| piece.id.0 == id));
+    if let Some(mut piece) = held {
+        piece.move_it(&gamepad_cursor.as_cursor());
+        if just_pressed(Action::Drop) || previous || next {
+            place_piece(&mut piece, &mut board, &mut history, &mut session);
+            gamepad_cursor.held = None;
+            dropped = true;
+        }
+    } else if gamepad_cursor.held.is_some() {
+        // The piece is gone with the previous level
+        gamepad_cursor.held = None;
+    }
+
+    // The shoulder buttons select the next or previous piece and put the cursor on it
+    if previous || next {
+        let mut ids: Vec<usize> = pieces.iter().map(|piece| piece.id.0).collect();
+        ids.sort_unstable();
+        let selected = cycle(&ids, selection.0, previous);
+        selection.set_if_neq(Selection(selected));
+        let first_square = selected
+            .and_then(|id| pieces.iter().find(|piece| piece.id.0 == id))
+            .and_then(|piece| piece.positions().first().copied());
+        if let Some(position) = first_square {
+            gamepad_cursor.position = position.truncate();
+        }
+        return;
+    }
+
+    // The buttons act on the held piece, or else on the piece under the cursor
+    let position = gamepad_cursor.position;
+    let mut pieces: Vec<PieceDataItem> = pieces.iter_mut().collect();
+    pieces.sort_by_key(|piece| (Some(piece.id.0) != gamepad_cursor.held, *piece.id));
+    let Some(mut piece) = pieces.into_iter().find(|piece| {
+        Some(piece.id.0) == gamepad_cursor.held
+            || (piece.is_even_odd(position) && can_handle(piece, Device::Gamepad))
+    }) else {
+        return;
+    };
+    if just_pressed(Action::Pick) && !dropped {
+        pick_piece(&mut piece, Device::Gamepad, &mut board, &mut history);
+        gamepad_cursor.held = Some(piece.id.0);
+        gamepad_cursor.grab_position = position;
+    }
+    for (action, rotation) in [
+        (Action::RotateCw, Rotation::Clockwise),
+        (Action::RotateCcw, Rotation::CounterClockwise),
+    ] {
+        if just_pressed(action) {
+            rotate_piece(&mut piece, rotation, &mut board, &mut history, &mut session);
+        }
+    }
+    for (action, axis) in [
+        (Action::Flip, FlipAxis::Horizontal),
+        (Action::FlipVertical, FlipAxis::Vertical),
+    ] {
+        if just_pressed(action) {
+            flip_piece(&mut piece, axis, &mut board, &mut history, &mut session);
+        }
+    }
+}
+
+/// Shows the virtual cursor once a gamepad is connected
+fn draw_pointer(
+    gamepads: Query<&Gamepad>,
+    gamepad_cursor: Res<GamepadCursor>,
+    mut pointers: Query<(&mut Transform, &mut Visibility), With<GamepadPointer>>,
+) {
+    let visibility = if gamepads.is_empty() {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for (mut transform, mut pointer_visibility) in pointers.iter_mut() {
+        pointer_visibility.set_if_neq(visibility);
+        let translation = gamepad_cursor.position.extend(transform.translation.z);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::input::gamepad::GamepadButton;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::piece::{kind::PieceKind, polyomino::Polyomino, spawn_piece};
+    use crate::test_utils::{app, press_button, tilt};
+
+    #[test]
+    fn test_place_piece_with_gamepad() {
+        // Given
+        // X X
+        // X X
+        let mut app = app(AppState::Playing);
+        // One cell per frame when the stick is fully tilted
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            100,
+        )))
+        .init_resource::<Selection>()
+        .insert_resource(Board::new(0, 0, 2, 2))
+        .add_plugins(GamepadPlugin);
+        let mut commands = app.world_mut().commands();
+        spawn_piece(
+            &mut commands,
+            0,
+            &Polyomino::new(PieceKind::TetrominoO, 3, 1),
+        );
+        let gamepad = app.world_mut().spawn(Gamepad::default()).id();
+        app.update();
+
+        // When
+        press_button(&mut app, gamepad, GamepadButton::RightTrigger);
+        press_button(&mut app, gamepad, GamepadButton::South);
+        for direction in [Vec2::NEG_X, Vec2::NEG_X, Vec2::NEG_Y, Vec2::NEG_X] {
+            tilt(&mut app, gamepad, direction);
+        }
+        press_button(&mut app, gamepad, GamepadButton::South);
+
+        // Then
+        assert_eq!(app.world().resource::<Selection>().0, Some(0));
+        assert_eq!(app.world().resource::<GamepadCursor>().held, None);
+        assert!(app.world().resource::<Board>().is_complete());
+        assert_eq!(app.world().resource::<Session>().drops, 1);
+
+        // When
+        press_button(&mut app, gamepad, GamepadButton::West);
+
+        // Then
+        assert_eq!(app.world().resource::<Session>().rotations, 1);
+        assert_eq!(app.world().resource::<History>().commands().len(), 2);
+    }
+}
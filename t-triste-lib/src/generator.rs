@@ -0,0 +1,421 @@
+use std::collections::BTreeSet;
+
+use bevy::math::IVec2;
+use thiserror::Error;
+
+use crate::difficulty::{analyze, MIN_DIFFICULTY};
+use crate::level::{Level, LevelBoard, LevelPiece, LevelShape};
+use crate::piece::{
+    board::Board, flip_shape, kind::PieceKind, orientations, rotate_shape, AllowedTransforms,
+    FlipAxis, PatternError, Rotation,
+};
+use crate::solver::{Solver, SolverPiece};
+
+/// Number of search steps spent partitioning the board before trying another partition
+const PARTITION_BUDGET: usize = 10_000;
+
+/// Number of solutions a generated puzzle must have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Uniqueness {
+    /// The puzzle has a single solution
+    #[default]
+    ExactlyOne,
+    /// The puzzle has at least one solution
+    AtLeastOne,
+}
+
+/// Settings of the puzzle generator
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorConfig {
+    /// ASCII pattern of the board, see `Board::from_pattern`
+    pub board_pattern: String,
+    /// Grid cell of the bottom left corner of the board
+    pub origin: (i32, i32),
+    /// Piece kinds the board is partitioned into, each one being used at most once
+    pub catalog: Vec<PieceKind>,
+    /// Number of solutions the puzzle must have. Solutions that are rotations or reflections
+    /// of each other on a symmetric board count as one.
+    pub uniqueness: Uniqueness,
+    /// Number of partitions tried before giving up
+    pub max_attempts: usize,
+}
+
+impl GeneratorConfig {
+    pub fn new(board_pattern: &str, catalog: &[PieceKind]) -> Self {
+        GeneratorConfig {
+            board_pattern: board_pattern.to_string(),
+            origin: (0, 0),
+            catalog: catalog.to_vec(),
+            uniqueness: Uniqueness::default(),
+            max_attempts: 100,
+        }
+    }
+}
+
+/// Reasons why no puzzle could be generated
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    #[error("invalid board pattern: {0:?}")]
+    Board(PatternError),
+    #[error("no puzzle found after {0} attempts")]
+    NoPuzzleFound(usize),
+}
+
+/// Generates a puzzle by partitioning the board into pieces of the catalog.
+/// The same configuration and seed always give the same level.
+pub fn generate(config: &GeneratorConfig, seed: u64) -> Result<Level, GeneratorError> {
+    let (x, y) = config.origin;
+    let board = Board::from_pattern(x, y, &config.board_pattern).map_err(GeneratorError::Board)?;
+    let mut random = Random::new(seed);
+
+    for _ in 0..config.max_attempts {
+        let mut free: BTreeSet<(i32, i32)> = board
+            .free_cells()
+            .into_iter()
+            .map(|(row, col)| (y + row as i32, x + col as i32))
+            .collect();
+        let mut used = vec![false; config.catalog.len()];
+        let mut kinds = vec![];
+        let mut budget = PARTITION_BUDGET;
+        if !partition(
+            &mut free,
+            &config.catalog,
+            &mut used,
+            &mut random,
+            &mut budget,
+            &mut kinds,
+        ) {
+            continue;
+        }
+
+        if config.uniqueness == Uniqueness::ExactlyOne
+            && count_distinct_solutions(&board, &kinds, 2) != 1
+        {
+            continue;
+        }
+        // The board was partitioned, so the puzzle has a solution and a score
+        let difficulty = analyze(&board, &solver_pieces(&kinds))
+            .score()
+            .unwrap_or(MIN_DIFFICULTY);
+        return Ok(level(config, seed, &kinds, difficulty, &mut random));
+    }
+    Err(GeneratorError::NoPuzzleFound(config.max_attempts))
+}
+
+/// Fills the free cells, ordered by `(y, x)`, with pieces of the catalog.
+/// Returns false when no partition was found within the budget.
+fn partition(
+    free: &mut BTreeSet<(i32, i32)>,
+    catalog: &[PieceKind],
+    used: &mut [bool],
+    random: &mut Random,
+    budget: &mut usize,
+    kinds: &mut Vec<PieceKind>,
+) -> bool {
+    let Some(&(y, x)) = free.first() else {
+        return true;
+    };
+    if *budget == 0 {
+        return false;
+    }
+    *budget -= 1;
+
+    let mut candidates: Vec<usize> = (0..catalog.len()).filter(|index| !used[*index]).collect();
+    random.shuffle(&mut candidates);
+    for index in candidates {
+        let mut shapes = orientations(
+            &catalog[index].shape(),
+            AllowedTransforms::RotationsAndFlips,
+        );
+        random.shuffle(&mut shapes);
+        for shape in shapes {
+            // The first free cell can only be covered by the first cell of a normalized shape
+            let anchor = IVec2::new(x, y) - shape[0];
+            let cells: Vec<(i32, i32)> = shape
+                .iter()
+                .map(|offset| (anchor.y + offset.y, anchor.x + offset.x))
+                .collect();
+            if !cells.iter().all(|cell| free.contains(cell)) {
+                continue;
+            }
+            cells.iter().for_each(|cell| {
+                free.remove(cell);
+            });
+            used[index] = true;
+            kinds.push(catalog[index]);
+            if partition(free, catalog, used, random, budget, kinds) {
+                return true;
+            }
+            kinds.pop();
+            used[index] = false;
+            free.extend(cells);
+        }
+    }
+    false
+}
+
+fn solver_pieces(kinds: &[PieceKind]) -> Vec<SolverPiece> {
+    kinds
+        .iter()
+        .map(|kind| SolverPiece::new(kind.shape(), AllowedTransforms::RotationsAndFlips))
+        .collect()
+}
+
+/// Index of a piece and the `(y, x)` cells it covers, sorted
+type CanonicalPlacement = (usize, Vec<(i32, i32)>);
+
+/// Counts the solutions of the puzzle, stopping at `limit`.
+/// Solutions that are a rotation or a reflection of another one through a symmetry
+/// of the board are the same solution for the player.
+fn count_distinct_solutions(board: &Board, kinds: &[PieceKind], limit: usize) -> usize {
+    let cells: Vec<IVec2> = board
+        .free_cells()
+        .into_iter()
+        .map(|(row, col)| board.origin + IVec2::new(col as i32, row as i32))
+        .collect();
+    let symmetries = board_symmetries(&cells);
+    let pieces = solver_pieces(kinds);
+
+    // A solution has at most one image per symmetry, so this is enough to see `limit` distinct ones
+    let solutions = Solver::new(board, &pieces).solve(Some((limit - 1) * symmetries.len() + 1));
+    let distinct: BTreeSet<Vec<CanonicalPlacement>> = solutions
+        .iter()
+        .map(|solution| {
+            symmetries
+                .iter()
+                .map(|(symmetry, offset)| {
+                    let mut placements: Vec<CanonicalPlacement> = solution
+                        .iter()
+                        .map(|placement| {
+                            let mut cells: Vec<(i32, i32)> = placement
+                                .cells()
+                                .into_iter()
+                                .map(|cell| transform(cell, *symmetry) + *offset)
+                                .map(|cell| (cell.y, cell.x))
+                                .collect();
+                            cells.sort();
+                            (placement.piece, cells)
+                        })
+                        .collect();
+                    placements.sort();
+                    placements
+                })
+                .min()
+                .unwrap_or_default()
+        })
+        .collect();
+    distinct.len().min(limit)
+}
+
+/// Returns the rotations and reflections, with the translation following them,
+/// that map the cells onto themselves. The identity is always one of them.
+fn board_symmetries(cells: &[IVec2]) -> Vec<(usize, IVec2)> {
+    let min = cells.iter().copied().reduce(IVec2::min).unwrap_or_default();
+    let mut expected: Vec<(i32, i32)> = cells.iter().map(|cell| (cell.y, cell.x)).collect();
+    expected.sort();
+    (0..8)
+        .filter_map(|symmetry| {
+            let transformed: Vec<IVec2> = cells
+                .iter()
+                .map(|cell| transform(*cell, symmetry))
+                .collect();
+            let offset = min
+                - transformed
+                    .iter()
+                    .copied()
+                    .reduce(IVec2::min)
+                    .unwrap_or_default();
+            let mut moved: Vec<(i32, i32)> = transformed
+                .iter()
+                .map(|cell| *cell + offset)
+                .map(|cell| (cell.y, cell.x))
+                .collect();
+            moved.sort();
+            (moved == expected).then_some((symmetry, offset))
+        })
+        .collect()
+}
+
+/// Applies one of the 8 rotations and reflections of the grid, around the origin
+fn transform(cell: IVec2, symmetry: usize) -> IVec2 {
+    let cell = if symmetry >= 4 {
+        IVec2::new(-cell.x, cell.y)
+    } else {
+        cell
+    };
+    (0..symmetry % 4).fold(cell, |cell, _| IVec2::new(cell.y, -cell.x))
+}
+
+/// Builds the level, the pieces being shuffled and laid out under the board
+fn level(
+    config: &GeneratorConfig,
+    seed: u64,
+    kinds: &[PieceKind],
+    difficulty: u8,
+    random: &mut Random,
+) -> Level {
+    let (x, y) = config.origin;
+    let mut kinds = kinds.to_vec();
+    random.shuffle(&mut kinds);
+
+    let mut next_x = x;
+    let pieces = kinds
+        .into_iter()
+        .map(|kind| {
+            let rotation = random.below(4) as u8;
+            let flipped = random.below(2) == 1;
+            let mut shape = kind.shape();
+            if flipped {
+                flip_shape(&mut shape, FlipAxis::Horizontal);
+            }
+            for _ in 0..rotation {
+                rotate_shape(&mut shape, Rotation::Clockwise);
+            }
+            let min = shape.iter().copied().reduce(IVec2::min).unwrap_or_default();
+            let max = shape.iter().copied().reduce(IVec2::max).unwrap_or_default();
+            // Leave an empty row between the board and the pieces
+            let anchor = IVec2::new(next_x, y - 2 - (max.y - min.y)) - min;
+            next_x += max.x - min.x + 2;
+            LevelPiece {
+                shape: LevelShape::Kind(kind),
+                color: None,
+                anchor: (anchor.x, anchor.y),
+                rotation,
+                flipped,
+            }
+        })
+        .collect();
+
+    Level {
+        name: format!("Generated #{seed}"),
+        author: "t-triste generator".to_string(),
+        difficulty,
+        board: LevelBoard {
+            origin: config.origin,
+            pattern: config.board_pattern.clone(),
+        },
+        pieces,
+    }
+}
+
+/// SplitMix64 generator. It is part of the crate so that a seed always gives the same
+/// puzzle, whatever the version of the dependencies.
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        Random(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound`
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            items.swap(index, self.below(index + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> GeneratorConfig {
+        let mut catalog = PieceKind::PENTOMINOES.to_vec();
+        catalog.extend(PieceKind::TETROMINOES);
+        GeneratorConfig::new("XXXXX\nXXXXX\nXXXXX\nXXXXX", &catalog)
+    }
+
+    #[test]
+    fn test_generated_puzzle_has_a_single_solution() {
+        // When
+        let mut level = generate(&config(), 42).unwrap();
+
+        // Then
+        let board = level.board().unwrap();
+        let pieces: Vec<SolverPiece> = level
+            .pieces()
+            .unwrap()
+            .iter()
+            .map(|piece| SolverPiece::new(piece.shape(), AllowedTransforms::RotationsAndFlips))
+            .collect();
+        let area: usize = pieces.iter().map(|piece| piece.shape.len()).sum();
+        assert_eq!(area, 20);
+        let kinds: Vec<PieceKind> = level
+            .pieces
+            .iter()
+            .filter_map(|piece| match piece.shape {
+                LevelShape::Kind(kind) => Some(kind),
+                LevelShape::Pattern(_) => None,
+            })
+            .collect();
+        assert_eq!(count_distinct_solutions(&board, &kinds, 10), 1);
+        // The other solutions are the mirror images of the first one
+        assert_eq!(Solver::new(&board, &pieces).count(None), 4);
+        assert_eq!(level.rate().unwrap(), level.difficulty);
+    }
+
+    #[test]
+    fn test_generation_is_reproducible() {
+        // When
+        let first = generate(&config(), 7).unwrap();
+        let second = generate(&config(), 7).unwrap();
+
+        // Then
+        assert_eq!(first, second);
+        assert_eq!(first.name, "Generated #7");
+        // The level can be shared as a level file
+        assert_eq!(Level::from_ron(&first.to_ron().unwrap()).unwrap(), first);
+    }
+
+    #[test]
+    fn test_board_symmetries() {
+        // Given
+        // X X X
+        // X X X
+        let rectangle = Board::new(0, 0, 2, 3);
+        // X X
+        // X X
+        let square = Board::new(0, 0, 2, 2);
+        // X .
+        // X X
+        let corner = Board::from_pattern(0, 0, "X.\nXX").unwrap();
+
+        // Then
+        let symmetries = |board: &Board| {
+            let cells: Vec<IVec2> = board
+                .free_cells()
+                .into_iter()
+                .map(|(row, col)| IVec2::new(col as i32, row as i32))
+                .collect();
+            board_symmetries(&cells).len()
+        };
+        assert_eq!(symmetries(&rectangle), 4);
+        assert_eq!(symmetries(&square), 8);
+        assert_eq!(symmetries(&corner), 2);
+    }
+
+    #[test]
+    fn test_generation_fails_without_partition() {
+        // Given
+        // The board area cannot be covered by tetrominoes only
+        let config = GeneratorConfig::new("XXX\nXXX", &PieceKind::TETROMINOES);
+
+        // Then
+        assert!(matches!(
+            generate(&config, 1),
+            Err(GeneratorError::NoPuzzleFound(100))
+        ));
+    }
+}
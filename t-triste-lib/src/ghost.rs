@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use bevy::sprite::Sprite;
+
+use crate::piece::{board::Board, cell_to_pixel, PieceDataReadOnly, PieceView, SQUARE_WIDTH};
+use crate::state::InGame;
+
+/// Plugin that shows where the moving pieces would land if they were dropped now
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_ghosts.run_if(in_state(InGame)));
+    }
+}
+
+/// Marker component for the squares of a ghost
+#[derive(Component)]
+struct GhostSquare;
+
+/// Where a moving piece would land
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ghost {
+    /// Grid cells the piece would be snapped to
+    pub cells: Vec<IVec2>,
+    /// Whether the piece would be incrusted in the board
+    pub valid: bool,
+}
+
+/// Returns where a piece would land if it was dropped now, or `None` if it is not moving
+pub fn ghost(board: Option<&Board>, piece: &dyn PieceView) -> Option<Ghost> {
+    if !piece.is_moving() {
+        return None;
+    }
+    let cells = piece.cells();
+    let valid = board.is_some_and(|board| board.cells_for(&cells).is_ok());
+    Some(Ghost { cells, valid })
+}
+
+// Systems
+/// Draws the ghosts again when a moving piece reaches another cell, or is dropped
+fn draw_ghosts(
+    mut commands: Commands,
+    pieces: Query<PieceDataReadOnly>,
+    board: Option<Res<Board>>,
+    query: Query<Entity, With<GhostSquare>>,
+    mut drawn: Local<Vec<Ghost>>,
+) {
+    let mut pieces: Vec<_> = pieces.iter().collect();
+    pieces.sort_by_key(|piece| *piece.id);
+    let ghosts: Vec<Ghost> = pieces
+        .iter()
+        .filter_map(|piece| ghost(board.as_deref(), piece))
+        .collect();
+    if ghosts == *drawn {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for ghost in ghosts.iter() {
+        let color = if ghost.valid {
+            Color::srgba(0.2, 0.8, 0.2, 0.4)
+        } else {
+            Color::srgba(0.9, 0.1, 0.1, 0.4)
+        };
+        for cell in ghost.cells.iter() {
+            commands.spawn((
+                Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(
+                        (SQUARE_WIDTH - 1) as f32,
+                        (SQUARE_WIDTH - 1) as f32,
+                    )),
+                    ..default()
+                },
+                // Above the hints and under the pieces
+                Transform::from_translation(cell_to_pixel(*cell).extend(0.6)),
+                GhostSquare,
+                DespawnOnExit(InGame),
+            ));
+        }
+    }
+    *drawn = ghosts;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::Cursor;
+    use crate::piece::{kind::PieceKind, polyomino::Polyomino, Piece};
+
+    #[test]
+    fn test_ghost_shows_snapped_placement() {
+        // Given
+        // X X X
+        // X X X
+        let mut board = Board::new(0, 0, 2, 3);
+        let mut piece = Polyomino::new(PieceKind::TetrominoO, 5, 0);
+        let other = Polyomino::new(PieceKind::TetrominoO, 0, 0);
+        board.place(1, &other.cells()).unwrap();
+        assert_eq!(ghost(Some(&board), &piece), None);
+
+        // When
+        piece.set_moving(true);
+        let mut cursor = Cursor {
+            current_pos: Vec2::new(-3.8, 0.3) * SQUARE_WIDTH as f32,
+            last_click_pos: Vec2::ZERO,
+            is_pressed: true,
+            is_dragging: true,
+        };
+        piece.move_it(&cursor);
+
+        // Then
+        assert_eq!(
+            ghost(Some(&board), &piece),
+            Some(Ghost {
+                cells: vec![
+                    IVec2::new(1, 0),
+                    IVec2::new(2, 0),
+                    IVec2::new(1, 1),
+                    IVec2::new(2, 1),
+                ],
+                valid: false,
+            })
+        );
+
+        // When
+        cursor.current_pos = Vec2::new(-4.2, 0.3) * SQUARE_WIDTH as f32;
+        board.remove(1);
+        piece.move_it(&cursor);
+
+        // Then
+        assert!(ghost(Some(&board), &piece).unwrap().valid);
+        assert!(!ghost(None, &piece).unwrap().valid);
+    }
+}
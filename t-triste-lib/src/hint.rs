@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy::sprite::Sprite;
+
+use crate::action::{Action, Actions};
+use crate::piece::{
+    board::Board, cell_to_pixel, AllowedTransforms, PieceId, PieceShape, SQUARE_WIDTH,
+};
+use crate::solver::{Placement, Solver, SolverPiece};
+use crate::state::{AppState, InGame};
+
+/// Plugin that lets the player ask for a hint, by default with the H key
+pub struct HintPlugin;
+
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hint>()
+            .add_systems(Update, request_hint.run_if(in_state(AppState::Playing)))
+            // After every system that may move the pieces on the board
+            .add_systems(
+                PostUpdate,
+                (
+                    clear_hint.run_if(resource_exists_and_changed::<Board>),
+                    draw_hint.run_if(resource_changed::<Hint>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Marker component for the squares highlighting a hint
+#[derive(Component)]
+struct HintHighlight;
+
+/// Resource holding the last hint given to the player
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Default)]
+pub enum Hint {
+    /// No hint asked, or the board changed since
+    #[default]
+    None,
+    /// A correct placement: `piece` is the `PieceId` of the piece
+    Placement(Placement),
+    /// The pieces already on the board cannot lead to a solution
+    DeadEnd,
+}
+
+/// Finds a placement of one of the pieces not on the board yet that leads to a solution,
+/// keeping the pieces already on the board where they are.
+/// Pieces are given as their id and their shape.
+pub fn find_hint(board: &Board, pieces: &[(usize, Vec<IVec2>)]) -> Hint {
+    let placed: HashSet<usize> = (0..board.nb_rows)
+        .flat_map(|row| (0..board.nb_cols).map(move |col| (row, col)))
+        .filter_map(|(row, col)| board.occupant_of(row, col))
+        .collect();
+    let remaining: Vec<&(usize, Vec<IVec2>)> = pieces
+        .iter()
+        .filter(|(id, _)| !placed.contains(id))
+        .collect();
+    let solver_pieces: Vec<SolverPiece> = remaining
+        .iter()
+        .map(|(_, shape)| SolverPiece::new(shape.clone(), AllowedTransforms::RotationsAndFlips))
+        .collect();
+
+    // The solver starts with the most constrained cell, which makes for a good hint
+    match Solver::new(board, &solver_pieces)
+        .first()
+        .and_then(|solution| solution.into_iter().next())
+    {
+        Some(placement) => Hint::Placement(Placement {
+            piece: remaining[placement.piece].0,
+            ..placement
+        }),
+        None => Hint::DeadEnd,
+    }
+}
+
+// Systems
+/// Forgets the hint once pieces are put on or taken off the board.
+/// The board is compared with the last one seen, as picking up a piece lying outside of it
+/// also marks it as changed.
+fn clear_hint(board: Res<Board>, mut hint: ResMut<Hint>, mut last_board: Local<Option<Board>>) {
+    if last_board.as_ref() == Some(&*board) {
+        return;
+    }
+    *last_board = Some(board.clone());
+    if *hint != Hint::None {
+        *hint = Hint::None;
+    }
+}
+
+fn request_hint(
+    actions: Res<Actions>,
+    board: Option<Res<Board>>,
+    pieces: Query<(&PieceId, &PieceShape)>,
+    mut hint: ResMut<Hint>,
+) {
+    if !actions.just_pressed(Action::Hint) {
+        return;
+    }
+    let Some(board) = board else {
+        return;
+    };
+    if board.is_complete() {
+        return;
+    }
+    let mut pieces: Vec<(usize, Vec<IVec2>)> = pieces
+        .iter()
+        .map(|(id, shape)| (id.0, shape.0.clone()))
+        .collect();
+    pieces.sort_by_key(|(id, _)| *id);
+    *hint = find_hint(&board, &pieces);
+}
+
+fn draw_hint(
+    mut commands: Commands,
+    hint: Res<Hint>,
+    board: Option<Res<Board>>,
+    query: Query<Entity, With<HintHighlight>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    let (cells, color) = match (hint.as_ref(), board) {
+        (Hint::Placement(placement), _) => (placement.cells(), Color::srgba(1., 1., 0.2, 0.6)),
+        // Highlight the pieces that have to move
+        (Hint::DeadEnd, Some(board)) => (
+            (0..board.nb_rows)
+                .flat_map(|row| (0..board.nb_cols).map(move |col| (row, col)))
+                .filter(|(row, col)| board.occupant_of(*row, *col).is_some())
+                .map(|(row, col)| board.origin + IVec2::new(col as i32, row as i32))
+                .collect(),
+            Color::srgba(1., 0., 0., 0.4),
+        ),
+        _ => return,
+    };
+    for cell in cells {
+        commands.spawn((
+            Sprite {
+                color,
+                custom_size: Some(Vec2::new(
+                    (SQUARE_WIDTH - 1) as f32,
+                    (SQUARE_WIDTH - 1) as f32,
+                )),
+                ..default()
+            },
+            // Between the board and the pieces
+            Transform::from_translation(cell_to_pixel(cell).extend(0.5)),
+            HintHighlight,
+            DespawnOnExit(InGame),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::KeyboardPlugin;
+    use crate::piece::{kind::PieceKind, polyomino::Polyomino, spawn_piece, Piece};
+    use crate::test_utils::{app, press};
+
+    fn shapes(pieces: &[Box<dyn Piece>]) -> Vec<(usize, Vec<IVec2>)> {
+        pieces
+            .iter()
+            .map(|piece| piece.shape())
+            .enumerate()
+            .collect()
+    }
+
+    #[test]
+    fn test_hint_completes_partial_placement() {
+        // Given
+        // X X X X
+        // X X X X
+        let mut board = Board::new(0, 0, 2, 4);
+        let color = PieceKind::TetrominoL.color();
+        let pieces: Vec<Box<dyn Piece>> = vec![
+            Box::new(Polyomino::from_pattern("XXX\nX..", color, 0, 0).unwrap()),
+            Box::new(Polyomino::new(PieceKind::TetrominoL, 10, 10)),
+        ];
+        board.place(0, &pieces[0].cells()).unwrap();
+
+        // When
+        let hint = find_hint(&board, &shapes(&pieces));
+
+        // Then
+        let Hint::Placement(placement) = hint else {
+            panic!("expected a placement, got {hint:?}");
+        };
+        assert_eq!(placement.piece, 1);
+        assert_eq!(
+            placement.cells(),
+            vec![
+                IVec2::new(1, 0),
+                IVec2::new(2, 0),
+                IVec2::new(3, 0),
+                IVec2::new(3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hint_reports_dead_end() {
+        // Given
+        // X X X X
+        // X X X X
+        let mut board = Board::new(0, 0, 2, 4);
+        let pieces: Vec<Box<dyn Piece>> = vec![
+            Box::new(Polyomino::new(PieceKind::TetrominoO, 1, 0)),
+            Box::new(Polyomino::new(PieceKind::TetrominoO, 10, 10)),
+        ];
+        board.place(0, &pieces[0].cells()).unwrap();
+
+        // When
+        let hint = find_hint(&board, &shapes(&pieces));
+
+        // Then
+        assert_eq!(hint, Hint::DeadEnd);
+    }
+
+    #[test]
+    fn test_hint_stays_until_the_board_changes() {
+        // Given
+        // X X
+        // X X
+        let mut app = app(AppState::Playing);
+        app.insert_resource(Board::new(0, 0, 2, 2))
+            .add_plugins((HintPlugin, KeyboardPlugin));
+        spawn_piece(
+            &mut app.world_mut().commands(),
+            0,
+            &Polyomino::new(PieceKind::TetrominoO, 3, 1),
+        );
+        app.update();
+        press(&mut app, KeyCode::KeyH);
+        assert!(matches!(app.world().resource::<Hint>(), Hint::Placement(_)));
+
+        // When
+        // The hinted piece is picked up and dropped outside of the board
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::ArrowLeft);
+        press(&mut app, KeyCode::Enter);
+
+        // Then
+        assert!(matches!(app.world().resource::<Hint>(), Hint::Placement(_)));
+
+        // When
+        for key in [KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowLeft] {
+            press(&mut app, key);
+        }
+        press(&mut app, KeyCode::Enter);
+
+        // Then
+        assert!(app.world().resource::<Board>().is_complete());
+        assert_eq!(*app.world().resource::<Hint>(), Hint::None);
+    }
+}
@@ -0,0 +1,574 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::action::{Action, Actions};
+use crate::piece::{
+    board::{Board, PlacementError},
+    FlipAxis, Piece, PieceData, Rotation,
+};
+use crate::session::Session;
+use crate::state::AppState;
+
+/// Plugin that records the actions of the player so they can be undone, by default with Ctrl+Z,
+/// and redone, by default with Ctrl+Y or Ctrl+Shift+Z
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>().add_systems(
+            Update,
+            (
+                clear_history.run_if(resource_added::<Board>),
+                undo_redo.run_if(in_state(AppState::Playing)),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// A quarter turn or a mirroring of a piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    /// The piece was turned a quarter
+    Rotate(Rotation),
+    /// The piece was mirrored
+    Flip(FlipAxis),
+}
+
+impl Turn {
+    /// Turns the piece, or turns it back when not going `forward`
+    fn apply(self, piece: &mut dyn Piece, forward: bool) {
+        match (self, forward) {
+            (Turn::Rotate(Rotation::Clockwise), true)
+            | (Turn::Rotate(Rotation::CounterClockwise), false) => piece.rotate(),
+            (Turn::Rotate(_), _) => piece.rotate_counter_clockwise(),
+            // Mirroring twice gives the piece back
+            (Turn::Flip(axis), _) => piece.flip(axis),
+        }
+    }
+}
+
+/// What the player did to a piece
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PieceAction {
+    /// The piece was dragged from one grid cell to another, and turned on the way
+    Drop {
+        from: IVec2,
+        to: IVec2,
+        turns: Vec<Turn>,
+    },
+    /// The piece was picked up from the board and dropped outside of it
+    Remove {
+        from: IVec2,
+        to: IVec2,
+        turns: Vec<Turn>,
+    },
+    /// The piece was turned where it lies
+    Turn(Turn),
+}
+
+/// A completed action, with what is needed to undo and redo it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceCommand {
+    /// `PieceId` of the piece
+    pub piece: usize,
+    pub action: PieceAction,
+    /// Whether the piece was incrusted in the board before the action
+    pub placed_before: bool,
+    /// Whether the piece was incrusted in the board after the action
+    pub placed_after: bool,
+}
+
+impl PieceCommand {
+    /// Reverts the action on the piece and on the board.
+    /// Nothing changes if the piece no longer fits where it was.
+    pub fn undo(
+        &self,
+        piece: &mut dyn Piece,
+        board: Option<&mut Board>,
+    ) -> Result<(), PlacementError> {
+        self.apply(piece, board, false)
+    }
+
+    /// Applies the action again on the piece and on the board.
+    /// Nothing changes if the piece no longer fits where it went.
+    pub fn redo(
+        &self,
+        piece: &mut dyn Piece,
+        board: Option<&mut Board>,
+    ) -> Result<(), PlacementError> {
+        self.apply(piece, board, true)
+    }
+
+    fn apply(
+        &self,
+        piece: &mut dyn Piece,
+        board: Option<&mut Board>,
+        forward: bool,
+    ) -> Result<(), PlacementError> {
+        let cells = piece.cells();
+        self.transform(piece, forward);
+        let placed = if forward {
+            self.placed_after
+        } else {
+            self.placed_before
+        };
+        let Some(board) = board else {
+            return Ok(());
+        };
+        let was_placed = board.contains(self.piece);
+        board.remove(self.piece);
+        if !placed {
+            return Ok(());
+        }
+        let Err(error) = board.place(self.piece, &piece.cells()) else {
+            return Ok(());
+        };
+        // The piece goes back where it was
+        self.transform(piece, !forward);
+        if was_placed {
+            let restored = board.place(self.piece, &cells);
+            debug_assert!(restored.is_ok(), "the cells of the piece were just freed");
+        }
+        Err(error)
+    }
+
+    fn transform(&self, piece: &mut dyn Piece, forward: bool) {
+        match (&self.action, forward) {
+            (PieceAction::Drop { to, turns, .. } | PieceAction::Remove { to, turns, .. }, true) => {
+                turns.iter().for_each(|turn| turn.apply(piece, true));
+                piece.set_anchor(*to);
+            }
+            (
+                PieceAction::Drop { from, turns, .. } | PieceAction::Remove { from, turns, .. },
+                false,
+            ) => {
+                turns.iter().rev().for_each(|turn| turn.apply(piece, false));
+                piece.set_anchor(*from);
+            }
+            (PieceAction::Turn(turn), _) => turn.apply(piece, forward),
+        }
+    }
+}
+
+/// A piece picked up by the player and not dropped yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Drag {
+    from: IVec2,
+    placed: bool,
+    /// Turns done while dragging, undone with the drop
+    turns: Vec<Turn>,
+}
+
+/// Resource holding the actions of the player on the current level
+#[derive(Resource, Debug, Default)]
+pub struct History {
+    done: Vec<PieceCommand>,
+    undone: Vec<PieceCommand>,
+    /// Pieces being dragged by their `PieceId`, each device can hold one
+    drags: HashMap<usize, Drag>,
+}
+
+impl History {
+    /// Returns the actions done so far, the oldest first, without the undone ones
+    pub fn commands(&self) -> &[PieceCommand] {
+        &self.done
+    }
+
+    /// Returns the undone actions, the next one to redo last
+    pub fn undone(&self) -> &[PieceCommand] {
+        &self.undone
+    }
+
+    /// Records a completed action. The undone actions cannot be redone anymore.
+    pub fn record(&mut self, command: PieceCommand) {
+        self.done.push(command);
+        self.undone.clear();
+    }
+
+    /// Records a turn of a piece. A piece turned while it is dragged is part of the drag,
+    /// so the turn is recorded with the drop.
+    pub fn turn(&mut self, piece: usize, turn: Turn, placed_before: bool, placed_after: bool) {
+        if let Some(drag) = self.drags.get_mut(&piece) {
+            drag.turns.push(turn);
+            return;
+        }
+        self.record(PieceCommand {
+            piece,
+            action: PieceAction::Turn(turn),
+            placed_before,
+            placed_after,
+        });
+    }
+
+    /// Remembers where a piece was picked up from, until it is dropped
+    pub fn start_drag(&mut self, piece: usize, from: IVec2, placed: bool) {
+        self.drags.insert(
+            piece,
+            Drag {
+                from,
+                placed,
+                turns: Vec::new(),
+            },
+        );
+    }
+
+    /// Records the drop of the piece being dragged, unless it ends as it started.
+    /// Returns whether the drop was recorded.
+    pub fn end_drag(&mut self, piece: usize, to: IVec2, placed: bool) -> bool {
+        let Some(drag) = self.drags.remove(&piece) else {
+            return false;
+        };
+        if drag.from == to && drag.placed == placed && drag.turns.is_empty() {
+            return false;
+        }
+        let (from, turns) = (drag.from, drag.turns);
+        let action = if drag.placed && !placed {
+            PieceAction::Remove { from, to, turns }
+        } else {
+            PieceAction::Drop { from, to, turns }
+        };
+        self.record(PieceCommand {
+            piece,
+            action,
+            placed_before: drag.placed,
+            placed_after: placed,
+        });
+        true
+    }
+
+    /// Moves the last action to the undone ones and returns it, for the caller to revert it.
+    /// Returns `None` when there is nothing to undo.
+    pub fn undo(&mut self) -> Option<PieceCommand> {
+        let command = self.done.pop()?;
+        self.undone.push(command.clone());
+        Some(command)
+    }
+
+    /// Moves the last undone action back to the done ones and returns it, for the caller
+    /// to apply it again. Returns `None` when there is nothing to redo.
+    pub fn redo(&mut self) -> Option<PieceCommand> {
+        let command = self.undone.pop()?;
+        self.done.push(command.clone());
+        Some(command)
+    }
+
+    /// Forgets every action, when another level starts
+    pub fn clear(&mut self) {
+        *self = History::default();
+    }
+}
+
+// Systems
+fn clear_history(mut history: ResMut<History>) {
+    history.clear();
+}
+
+fn undo_redo(
+    actions: Res<Actions>,
+    mut history: ResMut<History>,
+    mut pieces: Query<PieceData>,
+    mut board: Option<ResMut<Board>>,
+    mut session: ResMut<Session>,
+) {
+    // The piece being dragged is not part of the history yet
+    if pieces.iter().any(|piece| piece.drag.0.is_some()) {
+        return;
+    }
+    let redo = actions.just_pressed(Action::Redo);
+    let command = if redo {
+        history.undone().last()
+    } else if actions.just_pressed(Action::Undo) {
+        history.commands().last()
+    } else {
+        None
+    };
+    let Some(command) = command.cloned() else {
+        return;
+    };
+    let Some(mut piece) = pieces.iter_mut().find(|piece| piece.id.0 == command.piece) else {
+        return;
+    };
+    // The action stays where it is when the piece does not fit anymore
+    if redo {
+        if command.redo(&mut piece, board.as_deref_mut()).is_ok() {
+            history.redo();
+        }
+    } else if command.undo(&mut piece, board.as_deref_mut()).is_ok() {
+        history.undo();
+        session.undos += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::action::Device;
+    use crate::piece::{
+        kind::PieceKind, pick_piece, polyomino::Polyomino, rotate_piece, spawn_piece,
+        PieceDataReadOnly, PieceView,
+    };
+    use crate::place_piece;
+
+    /// Cells covered by the only piece
+    fn cells(world: &mut World) -> Vec<IVec2> {
+        world
+            .run_system_once(|pieces: Query<PieceDataReadOnly>| pieces.single().unwrap().cells())
+            .unwrap()
+    }
+
+    fn undo(world: &mut World) {
+        world
+            .resource_mut::<Actions>()
+            .press(Device::Keyboard, Action::Undo);
+        world.run_system_once(undo_redo).unwrap();
+        world.resource_mut::<Actions>().clear();
+    }
+
+    #[test]
+    fn test_undo_redo_drop() {
+        // Given
+        // X X X X
+        // X X X X
+        let mut board = Board::new(0, 0, 2, 4);
+        let mut pieces: Vec<Box<dyn Piece>> =
+            vec![Box::new(Polyomino::new(PieceKind::TetrominoO, 5, 5))];
+        let mut history = History::default();
+        history.start_drag(0, pieces[0].anchor(), false);
+        pieces[0].set_anchor(IVec2::new(1, 0));
+        board.place(0, &pieces[0].cells()).unwrap();
+        assert!(history.end_drag(0, IVec2::new(1, 0), true));
+
+        // When
+        let command = history.undo().unwrap();
+        command.undo(pieces[0].as_mut(), Some(&mut board)).unwrap();
+
+        // Then
+        assert_eq!(pieces[0].anchor(), IVec2::new(5, 5));
+        assert!(!board.contains(0));
+        assert!(history.undo().is_none());
+
+        // When
+        let command = history.redo().unwrap();
+        command.redo(pieces[0].as_mut(), Some(&mut board)).unwrap();
+
+        // Then
+        assert_eq!(pieces[0].anchor(), IVec2::new(1, 0));
+        assert!(board.contains(0));
+        assert_eq!(
+            history.commands(),
+            &[PieceCommand {
+                piece: 0,
+                action: PieceAction::Drop {
+                    from: IVec2::new(5, 5),
+                    to: IVec2::new(1, 0),
+                    turns: vec![],
+                },
+                placed_before: false,
+                placed_after: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_undo_rotation_puts_piece_back_in_board() {
+        // Given
+        // X X X
+        // X X X
+        let mut board = Board::new(0, 0, 2, 3);
+        let mut pieces: Vec<Box<dyn Piece>> =
+            vec![Box::new(Polyomino::new(PieceKind::TetrominoL, 0, -1))];
+        pieces[0].rotate();
+        let cells = pieces[0].cells();
+        board.place(0, &cells).unwrap();
+        let mut history = History::default();
+
+        // When
+        // The rotated piece no longer fits in the board
+        pieces[0].rotate();
+        board.remove(0);
+        history.record(PieceCommand {
+            piece: 0,
+            action: PieceAction::Turn(Turn::Rotate(Rotation::Clockwise)),
+            placed_before: true,
+            placed_after: false,
+        });
+        let command = history.undo().unwrap();
+        command.undo(pieces[0].as_mut(), Some(&mut board)).unwrap();
+
+        // Then
+        assert_eq!(pieces[0].cells(), cells);
+        assert!(board.contains(0));
+        assert_eq!(history.undone().len(), 1);
+    }
+
+    #[test]
+    fn test_record_clears_redo() {
+        // Given
+        let mut pieces: Vec<Box<dyn Piece>> =
+            vec![Box::new(Polyomino::new(PieceKind::TetrominoS, 0, 0))];
+        let mut history = History::default();
+        let flip = PieceCommand {
+            piece: 0,
+            action: PieceAction::Turn(Turn::Flip(FlipAxis::Horizontal)),
+            placed_before: false,
+            placed_after: false,
+        };
+        pieces[0].flip(FlipAxis::Horizontal);
+        history.record(flip.clone());
+        let command = history.undo().unwrap();
+        command.undo(pieces[0].as_mut(), None).unwrap();
+
+        // When
+        history.record(flip.clone());
+
+        // Then
+        assert!(history.undone().is_empty());
+        assert!(history.redo().is_none());
+        // A drag ending where it started is not recorded
+        history.start_drag(0, IVec2::ZERO, false);
+        assert!(!history.end_drag(0, IVec2::ZERO, false));
+        assert_eq!(history.commands(), &[flip]);
+    }
+
+    #[test]
+    fn test_undo_drag_with_rotation() {
+        // Given
+        // X X
+        // X X
+        // X X
+        let mut world = World::new();
+        world.insert_resource(Board::new(0, 0, 3, 2));
+        world.init_resource::<History>();
+        world.init_resource::<Session>();
+        world.init_resource::<Actions>();
+        spawn_piece(
+            &mut world.commands(),
+            0,
+            &Polyomino::new(PieceKind::TetrominoL, 5, 0),
+        );
+        world.flush();
+        let in_tray = cells(&mut world);
+
+        // When
+        // The piece is dropped in the board, then picked up, rotated so that it no longer fits
+        // and dropped at the same place
+        world
+            .run_system_once(
+                |mut pieces: Query<PieceData>,
+                 mut board: Option<ResMut<Board>>,
+                 mut history: ResMut<History>,
+                 mut session: ResMut<Session>| {
+                    let mut piece = pieces.single_mut().unwrap();
+                    // A click on the piece is not a move
+                    pick_piece(&mut piece, Device::Mouse, &mut board, &mut history);
+                    place_piece(&mut piece, &mut board, &mut history, &mut session);
+                    pick_piece(&mut piece, Device::Mouse, &mut board, &mut history);
+                    piece.drag.0 = Some(Vec2::ZERO);
+                    place_piece(&mut piece, &mut board, &mut history, &mut session);
+                    pick_piece(&mut piece, Device::Mouse, &mut board, &mut history);
+                    rotate_piece(
+                        &mut piece,
+                        Rotation::Clockwise,
+                        &mut board,
+                        &mut history,
+                        &mut session,
+                    );
+                    place_piece(&mut piece, &mut board, &mut history, &mut session);
+                },
+            )
+            .unwrap();
+        let in_board = in_tray
+            .iter()
+            .map(|cell| *cell - IVec2::new(5, 0))
+            .collect::<Vec<_>>();
+        assert!(!world.resource::<Board>().contains(0));
+        assert_eq!(world.resource::<History>().commands().len(), 2);
+        assert_eq!(world.resource::<Session>().drops, 2);
+        undo(&mut world);
+
+        // Then
+        assert_eq!(cells(&mut world), in_board);
+        assert!(world.resource::<Board>().contains(0));
+
+        // When
+        undo(&mut world);
+
+        // Then
+        assert_eq!(cells(&mut world), in_tray);
+        assert!(!world.resource::<Board>().contains(0));
+        assert!(world.resource::<History>().commands().is_empty());
+        assert_eq!(world.resource::<Session>().undos, 2);
+    }
+
+    #[test]
+    fn test_undo_is_refused_when_the_piece_no_longer_fits() {
+        // Given
+        // X X
+        // X X
+        let mut board = Board::new(0, 0, 2, 2);
+        let mut pieces: Vec<Box<dyn Piece>> = vec![
+            Box::new(Polyomino::new(PieceKind::TetrominoO, 5, 0)),
+            Box::new(Polyomino::new(PieceKind::TetrominoO, 0, 0)),
+        ];
+        let mut history = History::default();
+        history.start_drag(0, IVec2::ZERO, true);
+        history.end_drag(0, IVec2::new(5, 0), false);
+        // Another piece took the cells meanwhile
+        board.place(1, &pieces[1].cells()).unwrap();
+
+        // When
+        let command = history.commands()[0].clone();
+        let result = command.undo(pieces[0].as_mut(), Some(&mut board));
+
+        // Then
+        assert_eq!(result, Err(PlacementError::Overlap(1)));
+        assert_eq!(pieces[0].anchor(), IVec2::new(5, 0));
+        assert!(!board.contains(0));
+        assert!(board.contains(1));
+    }
+
+    #[test]
+    fn test_drags_of_several_devices() {
+        // Given
+        let mut history = History::default();
+        history.start_drag(0, IVec2::ZERO, false);
+        history.start_drag(1, IVec2::new(5, 0), true);
+
+        // When
+        history.turn(0, Turn::Flip(FlipAxis::Vertical), false, false);
+        let first = history.end_drag(0, IVec2::new(2, 0), true);
+        let second = history.end_drag(1, IVec2::new(7, 0), false);
+
+        // Then
+        assert!(first && second);
+        assert_eq!(
+            history.commands(),
+            &[
+                PieceCommand {
+                    piece: 0,
+                    action: PieceAction::Drop {
+                        from: IVec2::ZERO,
+                        to: IVec2::new(2, 0),
+                        turns: vec![Turn::Flip(FlipAxis::Vertical)],
+                    },
+                    placed_before: false,
+                    placed_after: true,
+                },
+                PieceCommand {
+                    piece: 1,
+                    action: PieceAction::Remove {
+                        from: IVec2::new(5, 0),
+                        to: IVec2::new(7, 0),
+                        turns: vec![],
+                    },
+                    placed_before: true,
+                    placed_after: false,
+                },
+            ]
+        );
+    }
+}
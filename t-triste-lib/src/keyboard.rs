@@ -0,0 +1,333 @@
+use bevy::prelude::*;
+use bevy::sprite::Sprite;
+
+use crate::action::{Action, Actions, Device};
+use crate::history::History;
+use crate::piece::{
+    board::Board, can_handle, cell_to_pixel, flip_piece, pick_piece, rotate_piece, FlipAxis, Piece,
+    PieceData, PieceId, PieceShape, PieceSquare, PieceView, Rotation, TrayAnchor, SQUARE_WIDTH,
+};
+use crate::place_piece;
+use crate::session::Session;
+use crate::state::AppState;
+
+/// Plugin that lets the player handle the pieces without a mouse. By default,
+/// Tab selects the next piece (Shift+Tab the previous one), the arrow keys move it one cell,
+/// R and E rotate it, F flips it, Enter places it and Backspace returns it to the tray
+pub struct KeyboardPlugin;
+
+impl Plugin for KeyboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>().add_systems(
+            Update,
+            (
+                clear_selection.run_if(resource_added::<Board>),
+                keyboard_piece.run_if(in_state(AppState::Playing)),
+                draw_selection.run_if(resource_changed::<Selection>),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Actions moving the selected piece, with the direction they move it to
+const MOVES: [(Action, IVec2); 4] = [
+    (Action::MoveUp, IVec2::Y),
+    (Action::MoveDown, IVec2::NEG_Y),
+    (Action::MoveLeft, IVec2::NEG_X),
+    (Action::MoveRight, IVec2::X),
+];
+
+/// Resource holding the `PieceId` of the piece selected with the keyboard
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Selection(pub Option<usize>);
+
+/// Marker component for the squares outlining the selected piece
+#[derive(Component)]
+struct SelectionHighlight;
+
+/// Returns the piece selected after `current` among the sorted piece ids,
+/// or before it when going `backwards`. The selection wraps around.
+pub fn cycle(ids: &[usize], current: Option<usize>, backwards: bool) -> Option<usize> {
+    let position = current.and_then(|current| ids.iter().position(|id| *id == current));
+    let next = match (position, backwards) {
+        (None, false) => 0,
+        (None, true) => ids.len().checked_sub(1)?,
+        (Some(position), false) => (position + 1) % ids.len(),
+        (Some(position), true) => (position + ids.len() - 1) % ids.len(),
+    };
+    ids.get(next).copied()
+}
+
+// Systems
+fn clear_selection(mut selection: ResMut<Selection>) {
+    selection.set_if_neq(Selection(None));
+}
+
+fn keyboard_piece(
+    actions: Res<Actions>,
+    mut selection: ResMut<Selection>,
+    mut pieces: Query<(PieceData, &TrayAnchor)>,
+    mut board: Option<ResMut<Board>>,
+    mut history: ResMut<History>,
+    mut session: ResMut<Session>,
+) {
+    let just_pressed = |action: Action| actions.just_pressed_by(Device::Keyboard, action);
+    let selected = selection
+        .0
+        .and_then(|id| pieces.iter_mut().find(|(piece, _)| piece.id.0 == id));
+
+    let previous = just_pressed(Action::SelectPrevious);
+    if previous || just_pressed(Action::SelectNext) {
+        // The piece being moved is dropped before selecting another one
+        if let Some((mut piece, _)) =
+            selected.filter(|(piece, _)| piece.held_by.0 == Some(Device::Keyboard))
+        {
+            place_piece(&mut piece, &mut board, &mut history, &mut session);
+        }
+        let mut ids: Vec<usize> = pieces.iter().map(|(piece, _)| piece.id.0).collect();
+        ids.sort_unstable();
+        let next = cycle(&ids, selection.0, previous);
+        selection.set_if_neq(Selection(next));
+        return;
+    }
+
+    // A piece held with another device is left alone
+    let Some((mut piece, tray)) = selected.filter(|(piece, _)| can_handle(piece, Device::Keyboard))
+    else {
+        return;
+    };
+    let index = piece.id.0;
+    if just_pressed(Action::Pick) && !piece.is_moving() {
+        pick_piece(&mut piece, Device::Keyboard, &mut board, &mut history);
+    }
+    for (_, direction) in MOVES.iter().filter(|(action, _)| just_pressed(*action)) {
+        if !piece.is_moving() {
+            pick_piece(&mut piece, Device::Keyboard, &mut board, &mut history);
+        }
+        let position = piece.drag.0.unwrap_or_default() + cell_to_pixel(*direction);
+        piece.drag.0 = Some(position);
+    }
+    for (action, rotation) in [
+        (Action::RotateCw, Rotation::Clockwise),
+        (Action::RotateCcw, Rotation::CounterClockwise),
+    ] {
+        if just_pressed(action) {
+            rotate_piece(&mut piece, rotation, &mut board, &mut history, &mut session);
+        }
+    }
+    for (action, axis) in [
+        (Action::Flip, FlipAxis::Horizontal),
+        (Action::FlipVertical, FlipAxis::Vertical),
+    ] {
+        if just_pressed(action) {
+            flip_piece(&mut piece, axis, &mut board, &mut history, &mut session);
+        }
+    }
+    if just_pressed(Action::Drop) {
+        place_piece(&mut piece, &mut board, &mut history, &mut session);
+    }
+    if just_pressed(Action::ReturnToTray) {
+        if !piece.is_moving() {
+            pick_piece(&mut piece, Device::Keyboard, &mut board, &mut history);
+        }
+        piece.set_anchor(tray.0);
+        history.end_drag(index, tray.0, false);
+    }
+}
+
+/// Outlines the squares of the selected piece. The outlines are children of the piece,
+/// so they follow it when it moves, rotates or flips.
+fn draw_selection(
+    mut commands: Commands,
+    selection: Res<Selection>,
+    pieces: Query<(Entity, &PieceId, &PieceShape)>,
+    query: Query<Entity, With<SelectionHighlight>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    let Some((entity, _, shape)) = pieces.iter().find(|(_, id, _)| Some(id.0) == selection.0)
+    else {
+        return;
+    };
+    commands.entity(entity).with_children(|parent| {
+        for (square, offset) in shape.0.iter().enumerate() {
+            parent.spawn((
+                Sprite {
+                    color: Color::srgb(0.3, 0.2, 0.3),
+                    custom_size: Some(Vec2::new(
+                        (SQUARE_WIDTH + 5) as f32,
+                        (SQUARE_WIDTH + 5) as f32,
+                    )),
+                    ..default()
+                },
+                // Under the squares of the piece
+                Transform::from_translation(cell_to_pixel(*offset).extend(-0.1)),
+                PieceSquare(square),
+                SelectionHighlight,
+            ));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::Cursor;
+    use crate::piece::{
+        board::BoardCompleted, kind::PieceKind, polyomino::Polyomino, release_piece, spawn_piece,
+        Drag, GridAnchor, PiecePlugin,
+    };
+    use crate::test_utils::{app, click, press};
+
+    fn anchor(app: &mut App, id: usize) -> IVec2 {
+        app.world_mut()
+            .query::<(&PieceId, &GridAnchor)>()
+            .iter(app.world())
+            .find(|(piece, _)| piece.0 == id)
+            .map(|(_, anchor)| anchor.0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cycle_selection() {
+        let ids = [0, 1, 2];
+        assert_eq!(cycle(&ids, None, false), Some(0));
+        assert_eq!(cycle(&ids, None, true), Some(2));
+        assert_eq!(cycle(&ids, Some(2), false), Some(0));
+        assert_eq!(cycle(&ids, Some(0), true), Some(2));
+        assert_eq!(cycle(&[], None, true), None);
+    }
+
+    #[test]
+    fn test_place_piece_with_keyboard() {
+        // Given
+        // X X
+        // X X
+        let mut app = app(AppState::Playing);
+        app.insert_resource(Board::new(0, 0, 2, 2))
+            .add_plugins(KeyboardPlugin);
+        let mut commands = app.world_mut().commands();
+        spawn_piece(
+            &mut commands,
+            0,
+            &Polyomino::new(PieceKind::TetrominoO, 3, 1),
+        );
+        spawn_piece(
+            &mut commands,
+            1,
+            &Polyomino::new(PieceKind::TetrominoL, 6, 0),
+        );
+        app.update();
+
+        // When
+        press(&mut app, KeyCode::Tab);
+        for key in [
+            KeyCode::ArrowLeft,
+            KeyCode::ArrowDown,
+            KeyCode::ArrowLeft,
+            KeyCode::ArrowLeft,
+        ] {
+            press(&mut app, key);
+        }
+        press(&mut app, KeyCode::Enter);
+
+        // Then
+        assert_eq!(app.world().resource::<Selection>().0, Some(0));
+        assert_eq!(anchor(&mut app, 0), IVec2::ZERO);
+        assert!(app.world().resource::<Board>().is_complete());
+        assert_eq!(app.world().resource::<History>().commands().len(), 1);
+        let board_completed = app.world().resource::<Messages<BoardCompleted>>();
+        assert_eq!(board_completed.len(), 1);
+
+        // When
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::KeyR);
+
+        // Then
+        assert_eq!(anchor(&mut app, 0), IVec2::new(3, 1));
+        assert!(!app.world().resource::<Board>().contains(0));
+        assert_eq!(app.world().resource::<Selection>().0, Some(1));
+        assert_eq!(app.world().resource::<Session>().rotations, 1);
+        assert_eq!(app.world().resource::<History>().commands().len(), 3);
+    }
+
+    #[test]
+    fn test_rotation_completes_the_board() {
+        // Given
+        // X X X X
+        let mut app = app(AppState::Playing);
+        app.insert_resource(Board::new(0, 0, 1, 4))
+            .add_plugins(KeyboardPlugin);
+        let mut commands = app.world_mut().commands();
+        spawn_piece(
+            &mut commands,
+            0,
+            &Polyomino::new(PieceKind::TetrominoI, 2, -1),
+        );
+        app.update();
+
+        // When
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::KeyR);
+
+        // Then
+        assert!(app.world().resource::<Board>().is_complete());
+        let board_completed = app.world().resource::<Messages<BoardCompleted>>();
+        assert_eq!(board_completed.len(), 1);
+    }
+
+    #[test]
+    fn test_mouse_leaves_the_keyboard_piece_alone() {
+        // Given
+        // X X
+        // X X
+        let mut app = app(AppState::Playing);
+        app.insert_resource(Board::new(0, 0, 2, 2))
+            // The mouse drags on an empty spot
+            .insert_resource(Cursor {
+                current_pos: Vec2::splat(1000.),
+                last_click_pos: Vec2::ZERO,
+                is_pressed: true,
+                is_dragging: true,
+            })
+            .add_plugins((KeyboardPlugin, PiecePlugin))
+            .add_systems(Update, crate::incrust_in_board.before(release_piece));
+        let mut commands = app.world_mut().commands();
+        spawn_piece(
+            &mut commands,
+            0,
+            &Polyomino::new(PieceKind::TetrominoO, 3, 1),
+        );
+        app.update();
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::ArrowLeft);
+
+        // When
+        click(&mut app, MouseButton::Left);
+
+        // Then
+        let drag = app
+            .world_mut()
+            .query::<&Drag>()
+            .single(app.world())
+            .unwrap()
+            .0;
+        assert_eq!(drag, Some(cell_to_pixel(IVec2::new(2, 1))));
+        assert!(app.world().resource::<History>().commands().is_empty());
+        assert_eq!(app.world().resource::<Session>().drops, 0);
+
+        // When
+        for key in [KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowLeft] {
+            press(&mut app, key);
+        }
+        press(&mut app, KeyCode::Enter);
+
+        // Then
+        assert_eq!(anchor(&mut app, 0), IVec2::ZERO);
+        assert!(app.world().resource::<Board>().is_complete());
+        assert_eq!(app.world().resource::<History>().commands().len(), 1);
+    }
+}
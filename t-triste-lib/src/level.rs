@@ -0,0 +1,434 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::difficulty::{analyze, Analysis};
+use crate::piece::{
+    board::Board, kind::PieceKind, polyomino::Polyomino, spawn_piece, AllowedTransforms, FlipAxis,
+    PatternError, Piece, PieceId,
+};
+use crate::solver::SolverPiece;
+use crate::state::InGame;
+
+/// Levels offered in the level select screen
+pub const LEVELS: &[&str] = &["levels/first_steps.level.ron"];
+
+/// Default color of pieces defined by a pattern without a color
+const DEFAULT_PIECE_COLOR: (f32, f32, f32) = (0.5, 0.5, 0.5);
+
+/// Plugin that loads levels from `.level.ron` files and sets up the board and the pieces
+pub struct LevelPlugin;
+
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Level>()
+            .register_asset_loader(LevelLoader)
+            .add_systems(Startup, load_levels)
+            .add_systems(Update, setup_level.run_if(in_state(InGame)))
+            .add_systems(OnExit(InGame), unload_level);
+    }
+}
+
+/// Resource holding the level currently played
+#[derive(Resource)]
+pub struct CurrentLevel(pub Handle<Level>);
+
+/// Resource holding the levels offered to the player, in the order of `LEVELS`
+#[derive(Resource)]
+pub struct LevelList(pub Vec<Handle<Level>>);
+
+/// A puzzle: the board to fill and the pieces available to fill it.
+///
+/// Levels are written in RON:
+/// ```text
+/// (
+///     name: "First steps",
+///     author: "ImFlog",
+///     difficulty: 1,
+///     board: (
+///         origin: (6, 5),
+///         pattern: "XXX\nXXX",
+///     ),
+///     pieces: [
+///         (shape: Kind(tetromino_l), anchor: (2, 2)),
+///         (shape: Pattern("XX"), color: Some((1., 0., 0.)), anchor: (2, 6), rotation: 1),
+///     ],
+/// )
+/// ```
+#[derive(Asset, TypePath, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Level {
+    /// Name displayed to the player
+    pub name: String,
+    /// Who designed the level
+    pub author: String,
+    /// How hard the level is, from 1 (easy) to 10, see `Level::rate`
+    pub difficulty: u8,
+    /// Shape and location of the board
+    pub board: LevelBoard,
+    /// Pieces available to fill the board
+    pub pieces: Vec<LevelPiece>,
+}
+
+/// Board description of a level
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LevelBoard {
+    /// Grid cell of the bottom left square of the board
+    pub origin: (i32, i32),
+    /// ASCII pattern of the board cells, see `Board::from_pattern`
+    pub pattern: String,
+}
+
+/// Piece description of a level
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LevelPiece {
+    /// Shape of the piece
+    pub shape: LevelShape,
+    /// sRGB color of the piece, defaults to the piece kind color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<(f32, f32, f32)>,
+    /// Grid cell the piece starts on
+    pub anchor: (i32, i32),
+    /// Number of clockwise quarter turns applied to the piece at start
+    #[serde(default)]
+    pub rotation: u8,
+    /// Whether the piece starts mirrored left to right (applied before rotating)
+    #[serde(default)]
+    pub flipped: bool,
+}
+
+/// Shape of a level piece
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LevelShape {
+    /// One of the predefined piece kinds, referenced by its stable name
+    Kind(PieceKind),
+    /// An ASCII pattern, see `PieceBuilder::from_pattern`
+    Pattern(String),
+}
+
+/// Reasons why a level cannot be loaded
+#[derive(Debug, Error)]
+pub enum LevelError {
+    #[error("could not read the level file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the level file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not write the level file: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("invalid board pattern: {0:?}")]
+    Board(PatternError),
+    #[error("invalid shape for piece {0}: {1:?}")]
+    Piece(usize, PatternError),
+    #[error("the pieces cannot fill the board")]
+    Unsolvable,
+}
+
+impl Level {
+    /// Parses a level from its RON representation and checks that its board and pieces are valid
+    pub fn from_ron(level: &str) -> Result<Self, LevelError> {
+        let level: Level = ron::de::from_str(level)?;
+        level.board()?;
+        level.pieces()?;
+        Ok(level)
+    }
+
+    /// Writes the level in its RON representation
+    pub fn to_ron(&self) -> Result<String, LevelError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    /// Builds the board of the level
+    pub fn board(&self) -> Result<Board, LevelError> {
+        let (x, y) = self.board.origin;
+        Board::from_pattern(x, y, &self.board.pattern).map_err(LevelError::Board)
+    }
+
+    /// Builds the pieces of the level, in their starting location and orientation
+    pub fn pieces(&self) -> Result<Vec<Box<dyn Piece>>, LevelError> {
+        self.pieces
+            .iter()
+            .enumerate()
+            .map(|(index, level_piece)| {
+                let (x, y) = level_piece.anchor;
+                let mut piece = match &level_piece.shape {
+                    LevelShape::Kind(kind) => {
+                        let mut piece = Polyomino::new(*kind, x, y);
+                        if let Some(color) = level_piece.color {
+                            piece = piece.with_color(srgb(color));
+                        }
+                        piece
+                    }
+                    LevelShape::Pattern(pattern) => Polyomino::from_pattern(
+                        pattern,
+                        srgb(level_piece.color.unwrap_or(DEFAULT_PIECE_COLOR)),
+                        x,
+                        y,
+                    )
+                    .map_err(|error| LevelError::Piece(index, error))?,
+                };
+                if level_piece.flipped {
+                    piece.flip(FlipAxis::Horizontal);
+                }
+                for _ in 0..level_piece.rotation % 4 {
+                    piece.rotate();
+                }
+                Ok(Box::new(piece) as Box<dyn Piece>)
+            })
+            .collect()
+    }
+
+    /// Analyzes how hard it is to fill the board with the pieces of the level
+    pub fn analyze(&self) -> Result<Analysis, LevelError> {
+        let pieces: Vec<SolverPiece> = self
+            .pieces()?
+            .iter()
+            .map(|piece| SolverPiece::new(piece.shape(), AllowedTransforms::RotationsAndFlips))
+            .collect();
+        Ok(analyze(&self.board()?, &pieces))
+    }
+
+    /// Computes the difficulty of the level and stores it in the level
+    pub fn rate(&mut self) -> Result<u8, LevelError> {
+        self.difficulty = self.analyze()?.score().ok_or(LevelError::Unsolvable)?;
+        Ok(self.difficulty)
+    }
+}
+
+fn srgb((red, green, blue): (f32, f32, f32)) -> Color {
+    Color::srgb(red, green, blue)
+}
+
+/// Loads `.level.ron` files into `Level` assets
+#[derive(Default)]
+struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = LevelError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Level, LevelError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Level::from_ron(&String::from_utf8_lossy(&bytes))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+// Systems
+fn load_levels(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(LevelList(
+        LEVELS.iter().map(|path| asset_server.load(*path)).collect(),
+    ));
+}
+
+fn setup_level(
+    mut commands: Commands,
+    mut asset_events: MessageReader<AssetEvent<Level>>,
+    current_level: Option<Res<CurrentLevel>>,
+    levels: Res<Assets<Level>>,
+    spawned: Query<Entity, With<PieceId>>,
+    mut pending: Local<bool>,
+) {
+    let Some(current_level) = current_level else {
+        return;
+    };
+    // A level was chosen, or the level file changed on disk with the `hot_reload` feature
+    let modified = asset_events
+        .read()
+        .filter(|event| event.is_modified(&current_level.0))
+        .count();
+    if current_level.is_changed() || modified > 0 {
+        *pending = true;
+    }
+    if !*pending {
+        return;
+    }
+    // The level may still be loading
+    let Some(level) = levels.get(&current_level.0) else {
+        return;
+    };
+    *pending = false;
+    // The loader already checked that the level is valid
+    let (Ok(board), Ok(pieces)) = (level.board(), level.pieces()) else {
+        return;
+    };
+    // Removing the board first lets the systems drawing it know it was replaced
+    commands.remove_resource::<Board>();
+    commands.insert_resource(board);
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (id, piece) in pieces.iter().enumerate() {
+        spawn_piece(&mut commands, id, piece.as_ref());
+    }
+}
+
+/// The pieces are despawned with the other entities of the level
+fn unload_level(mut commands: Commands) {
+    commands.remove_resource::<Board>();
+    commands.remove_resource::<CurrentLevel>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_level_is_valid() {
+        // Given
+        let file = include_str!("../../t-triste/assets/levels/first_steps.level.ron");
+
+        // When
+        let level = Level::from_ron(file).unwrap();
+
+        // Then
+        assert_eq!(level.name, "First steps");
+        let board = level.board().unwrap();
+        let pieces = level.pieces().unwrap();
+        let area: usize = pieces.iter().map(|piece| piece.cells().len()).sum();
+        assert_eq!(area, board.free_cells().len());
+    }
+
+    #[test]
+    fn test_rate_level() {
+        // Given
+        let file = include_str!("../../t-triste/assets/levels/first_steps.level.ron");
+        let mut level = Level::from_ron(file).unwrap();
+        let mut unsolvable = Level::from_ron(
+            r#"(name: "", author: "", difficulty: 1,
+                board: (origin: (0, 0), pattern: "XX\nXX"),
+                pieces: [(shape: Kind(tetromino_s), anchor: (0, 0))])"#,
+        )
+        .unwrap();
+
+        // When
+        let difficulty = level.rate().unwrap();
+
+        // Then
+        assert_eq!(difficulty, 1);
+        assert_eq!(level.difficulty, difficulty);
+        assert_eq!(level.analyze().unwrap().stats.solutions, 100);
+        assert!(matches!(unsolvable.rate(), Err(LevelError::Unsolvable)));
+    }
+
+    #[test]
+    fn test_level_pieces_orientation() {
+        // Given
+        let level = Level::from_ron(
+            r#"(
+                name: "Orientation",
+                author: "tests",
+                difficulty: 1,
+                board: (origin: (0, 0), pattern: "XXXX\nXXXX"),
+                pieces: [
+                    (shape: Kind(tetromino_l), anchor: (1, 1), rotation: 1),
+                    (shape: Pattern("XX.\n.XX"), anchor: (5, 5), flipped: true),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        // When
+        let pieces = level.pieces().unwrap();
+
+        // Then
+        // * * *
+        // *
+        assert_eq!(
+            pieces[0].cells(),
+            vec![
+                IVec2::new(1, 3),
+                IVec2::new(1, 2),
+                IVec2::new(2, 3),
+                IVec2::new(3, 3),
+            ]
+        );
+        assert_eq!(pieces[0].color(), PieceKind::TetrominoL.color());
+        // . * *
+        // * * .
+        assert_eq!(
+            pieces[1].cells(),
+            vec![
+                IVec2::new(6, 5),
+                IVec2::new(5, 5),
+                IVec2::new(7, 6),
+                IVec2::new(6, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_level_with_holed_board() {
+        // Given
+        let pieces: Vec<String> = PieceKind::PENTOMINOES
+            .iter()
+            .map(|kind| format!("(shape: Kind({}), anchor: (-8, 0))", kind.name()))
+            .collect();
+        let level = format!(
+            r#"(
+                name: "Scott's puzzle",
+                author: "tests",
+                difficulty: 3,
+                board: (origin: (-4, -4), pattern: "
+                    XXXXXXXX
+                    XXXXXXXX
+                    XXXXXXXX
+                    XXX..XXX
+                    XXX..XXX
+                    XXXXXXXX
+                    XXXXXXXX
+                    XXXXXXXX
+                "),
+                pieces: [{}],
+            )"#,
+            pieces.join(", ")
+        );
+
+        // When
+        let level = Level::from_ron(&level).unwrap();
+
+        // Then
+        let board = level.board().unwrap();
+        assert_eq!((board.nb_rows, board.nb_cols), (8, 8));
+        assert_eq!(board.free_cells().len(), 60);
+        assert!(!board.is_cell_free(3, 3));
+        assert_eq!(level.pieces().unwrap().len(), 12);
+    }
+
+    #[test]
+    fn test_level_errors() {
+        let level = |board: &str, shape: &str| {
+            format!(
+                r#"(name: "", author: "", difficulty: 1,
+                    board: (origin: (0, 0), pattern: "{board}"),
+                    pieces: [(shape: {shape}, anchor: (0, 0))])"#
+            )
+        };
+        assert!(matches!(
+            Level::from_ron(&level("X.\\nX?", "Kind(pentomino_x)")),
+            Err(LevelError::Board(PatternError::InvalidCharacter('?')))
+        ));
+        assert!(matches!(
+            Level::from_ron(&level("XX", "Pattern(\"X?\")")),
+            Err(LevelError::Piece(0, PatternError::InvalidCharacter('?')))
+        ));
+        assert!(matches!(
+            Level::from_ron(&level("XX", "Kind(hexomino)")),
+            Err(LevelError::Ron(_))
+        ));
+    }
+}
@@ -1,12 +1,30 @@
-mod cursor;
-mod piece;
+pub mod action;
+pub mod cursor;
+pub mod difficulty;
+pub mod gamepad;
+pub mod generator;
+pub mod ghost;
+pub mod hint;
+pub mod history;
+pub mod keyboard;
+pub mod level;
+pub mod piece;
+pub mod session;
+pub mod solver;
+pub mod state;
+/// Helpers for the tests driving an app with the inputs of the player
+#[cfg(test)]
+mod test_utils;
 
+use action::{Action, Actions, Device};
 use bevy::prelude::*;
 use bevy::window::{Window, WindowPlugin};
+use history::History;
 use piece::{
     board::{self, Board},
-    GameState, SQUARE_WIDTH,
+    Piece, PieceData, PieceDataItem, PieceView, SQUARE_WIDTH,
 };
+use session::Session;
 
 // Plugin
 pub struct GamePlugin;
@@ -24,10 +42,24 @@ impl Plugin for GamePlugin {
                 ..default()
             }))
             .add_systems(Startup, setup_camera)
+            .add_plugins((action::ActionPlugin, action::BindingsPlugin))
+            .add_plugins(state::StatePlugin)
             .add_plugins(cursor::CursorPlugin)
             .add_plugins(board::BoardPlugin)
             .add_plugins(piece::PiecePlugin)
-            .add_systems(Update, incrust_in_board);
+            .add_plugins(level::LevelPlugin)
+            .add_plugins(hint::HintPlugin)
+            .add_plugins(ghost::GhostPlugin)
+            .add_plugins(history::HistoryPlugin)
+            .add_plugins(keyboard::KeyboardPlugin)
+            .add_plugins(gamepad::GamepadPlugin)
+            .add_plugins(session::SessionPlugin)
+            .add_systems(
+                Update,
+                incrust_in_board
+                    .before(piece::release_piece)
+                    .run_if(in_state(state::AppState::Playing)),
+            );
     }
 }
 
@@ -37,43 +69,60 @@ fn setup_camera(mut commands: Commands) {
 }
 
 fn incrust_in_board(
-    mut game_state: NonSendMut<GameState>,
-    board: Option<Res<Board>>,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mut pieces: Query<PieceData>,
+    board: Option<ResMut<Board>>,
+    actions: Res<Actions>,
+    mut history: ResMut<History>,
+    mut session: ResMut<Session>,
 ) {
-    if !mouse_button_input.just_released(MouseButton::Left) || board.is_none() {
+    if !actions.just_pressed_by(Device::Mouse, Action::Drop) {
         return;
     }
+    let Some(mut board) = board else {
+        return;
+    };
 
-    let board = board.unwrap();
+    // We take the piece held with the mouse
+    let Some(mut moving_piece) = pieces
+        .iter_mut()
+        .find(|piece| piece.held_by.0 == Some(Device::Mouse))
+    else {
+        return;
+    };
+    drop_piece(&mut moving_piece, &mut board, &mut history, &mut session);
+}
 
-    // The issue was that the code expected pixel perfect placement.
-    // Add a 5% acceptance factor.
-    // We could put this in a method to clean up the code ?
-    let adjusted_min_x = board.min_x * 0.95;
-    let adjusted_min_y = board.min_y * 0.95;
-    let adjusted_max_x = board.max_x * 1.05;
-    let adjusted_max_y = board.max_y * 1.05;
+/// Drops a moving piece where it is. The piece is only incrusted when every square
+/// lands on a free cell. It keeps moving until it is released.
+pub(crate) fn drop_piece(
+    piece: &mut PieceDataItem,
+    board: &mut Board,
+    history: &mut History,
+    session: &mut Session,
+) {
+    let index = piece.id.0;
+    let placed = board.place(index, &piece.cells()).is_ok();
+    // A piece released as it was picked up is not a move
+    if history.end_drag(index, piece.anchor(), placed) {
+        session.drops += 1;
+    }
+    if placed {
+        piece.snap();
+    }
+}
 
-    // We take the first moving piece
-    // TODO: This could be improved
-    let moving_piece_optional = game_state.0.iter_mut().find(|piece| piece.is_moving());
-    if moving_piece_optional.is_none() {
+/// Drops a moving piece where it is and stops moving it
+pub(crate) fn place_piece(
+    piece: &mut PieceDataItem,
+    board: &mut Option<ResMut<Board>>,
+    history: &mut History,
+    session: &mut Session,
+) {
+    if !piece.is_moving() {
         return;
     }
-    let moving_piece = moving_piece_optional.unwrap();
-
-    // TODO: Find the exact board position that is being filled
-    let in_board = moving_piece.positions().iter().all(|t| {
-        adjusted_min_x <= t.x
-            && t.x <= adjusted_max_x
-            && adjusted_min_y <= t.y
-            && t.y <= adjusted_max_y
-    });
-
-    if in_board {
-        moving_piece.snap();
-        // TODO: we are once again iterating over the transform. This is not efficient.
-        // TODO: Save the board squares that are filled.
+    if let Some(board) = board.as_mut() {
+        drop_piece(piece, board, history, session);
     }
+    piece.set_moving(false);
 }
@@ -1,7 +1,11 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 use bevy::sprite::Sprite;
 
-use crate::piece::SQUARE_WIDTH;
+use crate::piece::{cell_to_pixel, PatternError, SQUARE_WIDTH};
+use crate::session::Session;
+use crate::state::InGame;
 
 use super::piece_builder::PieceBuilder;
 
@@ -10,8 +14,13 @@ pub struct BoardPlugin;
 
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Board::new(300, 250))
-            .add_systems(Startup, draw_board);
+        app.add_message::<BoardCompleted>()
+            .add_systems(Update, draw_board.run_if(resource_added::<Board>))
+            // After every system that may fill the board: drops, turns, undo and redo
+            .add_systems(
+                PostUpdate,
+                detect_completion.run_if(resource_exists_and_changed::<Board>),
+            );
     }
 }
 
@@ -19,60 +28,243 @@ impl Plugin for BoardPlugin {
 #[derive(Component)]
 struct BoardPosition;
 
+/// Message sent when every cell of the board is covered by exactly one piece
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardCompleted {
+    /// Time elapsed since the level started
+    pub elapsed: Duration,
+    /// Number of pieces dropped by the player before completing the board
+    pub moves: u32,
+}
+
+/// Reasons why a piece cannot be placed on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementError {
+    /// At least one square of the piece hangs off the board or over one of its holes
+    OutOfBoard,
+    /// At least one square of the piece covers a blocked cell
+    Blocked,
+    /// At least one square of the piece covers a cell filled by another piece
+    Overlap(usize),
+}
+
+/// State of a cell of the board grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    /// Not part of the board
+    Hole,
+    /// Part of the board but cannot be filled
+    Blocked,
+    /// Waiting for a piece
+    Free,
+    /// Filled by the piece with the given `PieceId`
+    Filled(usize),
+}
+
 /// Represents the game board where pieces can be placed.
-/// Currently a fixed 3x5 grid:
+/// The board can have any shape, for instance a 3x5 rectangle with a hole
+/// and a blocked cell:
 /// ```text
-/// * * * * *
-/// * * * * *
-/// * * * * *
+/// X X X X X
+/// X X . X #
+/// X X X X X
 /// ```
-#[derive(Resource)]
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
 pub struct Board {
-    /// Positions of all squares that make up the board
-    pub positions: Vec<Vec3>,
-    /// Minimum X coordinate of the board (left edge)
-    pub min_x: f32,
-    /// Minimum Y coordinate of the board (bottom edge)
-    pub min_y: f32,
-    /// Maximum X coordinate of the board (right edge)
-    pub max_x: f32,
-    /// Maximum Y coordinate of the board (top edge)
-    pub max_y: f32,
-    // TODO: Track which positions are filled - vec[bool[]] ?
+    /// Grid cell of the bottom left corner of the board
+    pub origin: IVec2,
+    /// Number of rows of the board bounding box
+    pub nb_rows: usize,
+    /// Number of columns of the board bounding box
+    pub nb_cols: usize,
+    /// Cells grid indexed by `[row][col]`
+    cells: Vec<Vec<Cell>>,
 }
 
 impl Board {
-    fn new(start_x: i32, start_y: i32) -> Self {
-        let nb_rows = 3;
-        let nb_cols = 5;
-        let mut positions = vec![];
-        for i in 0..nb_rows {
-            positions.append(&mut PieceBuilder::new_horizontal_rectangle(
-                start_x,
-                start_y + (i * SQUARE_WIDTH),
-                nb_cols,
-                0.,
-            ));
-        }
+    /// Creates a rectangular board
+    pub fn new(start_x: i32, start_y: i32, nb_rows: usize, nb_cols: usize) -> Self {
         Board {
-            positions,
-            min_x: start_x as f32,
-            min_y: start_y as f32,
-            max_x: (start_x + (nb_cols * SQUARE_WIDTH)) as f32,
-            max_y: (start_y + (nb_rows * SQUARE_WIDTH)) as f32,
+            origin: IVec2::new(start_x, start_y),
+            nb_rows,
+            nb_cols,
+            cells: vec![vec![Cell::Free; nb_cols]; nb_rows],
         }
     }
 
+    /// Creates a board from an ASCII pattern where `X` is a cell to fill,
+    /// `#` a blocked cell and `.` a hole, see `PieceBuilder::from_pattern`
+    pub fn from_pattern(start_x: i32, start_y: i32, pattern: &str) -> Result<Self, PatternError> {
+        let squares = PieceBuilder::parse_pattern(pattern, &['X', '#'])?;
+        if !squares.iter().any(|(_, symbol)| *symbol == 'X') {
+            return Err(PatternError::Empty);
+        }
+        let max = squares
+            .iter()
+            .map(|(cell, _)| *cell)
+            .reduce(IVec2::max)
+            .unwrap_or_default();
+        let (nb_rows, nb_cols) = (max.y as usize + 1, max.x as usize + 1);
+        let mut cells = vec![vec![Cell::Hole; nb_cols]; nb_rows];
+        for (cell, symbol) in squares {
+            cells[cell.y as usize][cell.x as usize] = match symbol {
+                '#' => Cell::Blocked,
+                _ => Cell::Free,
+            };
+        }
+        Ok(Board {
+            origin: IVec2::new(start_x, start_y),
+            nb_rows,
+            nb_cols,
+            cells,
+        })
+    }
+
     #[cfg(test)]
     pub fn new_for_tests(start_x: i32, start_y: i32) -> Self {
-        Self::new(start_x, start_y)
+        Self::new(start_x, start_y, 3, 5)
+    }
+
+    /// Returns the pixel positions of all squares that can be filled
+    pub fn positions(&self) -> Vec<Vec3> {
+        self.positions_of(|cell| matches!(cell, Cell::Free | Cell::Filled(_)))
+    }
+
+    /// Returns the pixel positions of all blocked squares
+    pub fn blocked_positions(&self) -> Vec<Vec3> {
+        self.positions_of(|cell| cell == Cell::Blocked)
+    }
+
+    fn positions_of(&self, filter: impl Fn(Cell) -> bool) -> Vec<Vec3> {
+        let mut positions = vec![];
+        for row in 0..self.nb_rows {
+            positions.extend(
+                PieceBuilder::new_horizontal_rectangle(
+                    self.origin.x,
+                    self.origin.y + row as i32,
+                    self.nb_cols as i32,
+                )
+                .into_iter()
+                .enumerate()
+                .filter(|(col, _)| filter(self.cells[row][*col]))
+                .map(|(_, cell)| cell_to_pixel(cell).extend(0.)),
+            );
+        }
+        positions
+    }
+
+    /// Returns the `(row, col)` of a grid cell, or `None` if the cell is outside of
+    /// the board bounding box
+    pub fn cell_at(&self, cell: IVec2) -> Option<(usize, usize)> {
+        let relative = cell - self.origin;
+        if relative.x < 0 || relative.y < 0 {
+            return None;
+        }
+        let (row, col) = (relative.y as usize, relative.x as usize);
+        (row < self.nb_rows && col < self.nb_cols).then_some((row, col))
+    }
+
+    /// Returns the state of a cell, cells outside of the board being holes
+    pub fn cell(&self, row: usize, col: usize) -> Cell {
+        self.cells
+            .get(row)
+            .and_then(|r| r.get(col))
+            .copied()
+            .unwrap_or(Cell::Hole)
+    }
+
+    /// Returns true if the cell is part of the board and no piece fills it
+    pub fn is_cell_free(&self, row: usize, col: usize) -> bool {
+        self.cell(row, col) == Cell::Free
+    }
+
+    /// Returns the index of the piece filling a cell, if any
+    pub fn occupant_of(&self, row: usize, col: usize) -> Option<usize> {
+        match self.cell(row, col) {
+            Cell::Filled(piece) => Some(piece),
+            _ => None,
+        }
+    }
+
+    /// Returns true when the piece fills cells of the board
+    pub fn contains(&self, piece: usize) -> bool {
+        self.cells
+            .iter()
+            .flatten()
+            .any(|cell| *cell == Cell::Filled(piece))
+    }
+
+    /// Returns the `(row, col)` of every cell that is not filled yet
+    pub fn free_cells(&self) -> Vec<(usize, usize)> {
+        (0..self.nb_rows)
+            .flat_map(|row| (0..self.nb_cols).map(move |col| (row, col)))
+            .filter(|&(row, col)| self.is_cell_free(row, col))
+            .collect()
+    }
+
+    /// Returns true when every cell of the board is filled
+    pub fn is_complete(&self) -> bool {
+        !self.cells.iter().flatten().any(|cell| *cell == Cell::Free)
+    }
+
+    /// Checks that every grid cell lands on a free cell of the board and returns their `(row, col)`
+    pub fn cells_for(&self, cells: &[IVec2]) -> Result<Vec<(usize, usize)>, PlacementError> {
+        cells
+            .iter()
+            .map(|cell| {
+                let (row, col) = self.cell_at(*cell).ok_or(PlacementError::OutOfBoard)?;
+                match self.cell(row, col) {
+                    Cell::Hole => Err(PlacementError::OutOfBoard),
+                    Cell::Blocked => Err(PlacementError::Blocked),
+                    Cell::Filled(occupant) => Err(PlacementError::Overlap(occupant)),
+                    Cell::Free => Ok((row, col)),
+                }
+            })
+            .collect()
+    }
+
+    /// Fills the given grid cells with a piece.
+    /// Nothing is filled if the piece hangs off the board or overlaps another piece.
+    pub fn place(&mut self, piece: usize, cells: &[IVec2]) -> Result<(), PlacementError> {
+        for (row, col) in self.cells_for(cells)? {
+            self.cells[row][col] = Cell::Filled(piece);
+        }
+        Ok(())
+    }
+
+    /// Frees every cell filled by a piece
+    pub fn remove(&mut self, piece: usize) {
+        self.cells
+            .iter_mut()
+            .flatten()
+            .filter(|cell| **cell == Cell::Filled(piece))
+            .for_each(|cell| *cell = Cell::Free);
     }
 }
 
 // Systems
-fn draw_board(board: Res<Board>, mut commands: Commands) {
+fn draw_board(
+    board: Res<Board>,
+    mut commands: Commands,
+    query: Query<Entity, With<BoardPosition>>,
+) {
+    // A new board replaces the previous one
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
     let color = Color::srgb(0.60, 0.40, 0.);
-    board.positions.iter().for_each(|position| {
+    let blocked_color = Color::srgb(0.30, 0.20, 0.);
+    let squares = board
+        .positions()
+        .into_iter()
+        .map(|position| (position, color))
+        .chain(
+            board
+                .blocked_positions()
+                .into_iter()
+                .map(|position| (position, blocked_color)),
+        );
+    squares.for_each(|(position, color)| {
         commands.spawn((
             Sprite {
                 color,
@@ -82,8 +274,160 @@ fn draw_board(board: Res<Board>, mut commands: Commands) {
                 )),
                 ..default()
             },
-            Transform::from_translation(*position),
+            Transform::from_translation(position),
             BoardPosition,
+            DespawnOnExit(InGame),
         ));
     });
 }
+
+/// Sends `BoardCompleted` once when the last cell of the board gets filled,
+/// whatever filled it
+fn detect_completion(
+    board: Res<Board>,
+    session: Res<Session>,
+    mut completed: Local<bool>,
+    mut board_completed: MessageWriter<BoardCompleted>,
+) {
+    let complete = board.is_complete();
+    if complete && !*completed {
+        board_completed.write(BoardCompleted {
+            elapsed: session.elapsed,
+            moves: session.drops,
+        });
+    }
+    *completed = complete;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_position(row: i32, col: i32) -> IVec2 {
+        IVec2::new(2 + col, 1 + row)
+    }
+
+    #[test]
+    fn test_place_fills_cells() {
+        // Given
+        let mut board = Board::new_for_tests(2, 1);
+
+        // When
+        let result = board.place(2, &[board_position(0, 0), board_position(1, 0)]);
+
+        // Then
+        assert_eq!(result, Ok(()));
+        assert_eq!(board.occupant_of(0, 0), Some(2));
+        assert_eq!(board.occupant_of(1, 0), Some(2));
+        assert!(board.is_cell_free(0, 1));
+        assert_eq!(board.free_cells().len(), 13);
+    }
+
+    #[test]
+    fn test_place_rejects_overlap() {
+        // Given
+        let mut board = Board::new_for_tests(2, 1);
+        board.place(0, &[board_position(0, 0)]).unwrap();
+
+        // When
+        let result = board.place(1, &[board_position(0, 1), board_position(0, 0)]);
+
+        // Then
+        assert_eq!(result, Err(PlacementError::Overlap(0)));
+        assert!(board.is_cell_free(0, 1));
+    }
+
+    #[test]
+    fn test_place_rejects_out_of_board() {
+        // Given
+        let mut board = Board::new_for_tests(2, 1);
+
+        // When
+        let result = board.place(0, &[board_position(2, 4), board_position(3, 4)]);
+
+        // Then
+        assert_eq!(result, Err(PlacementError::OutOfBoard));
+        assert_eq!(board.free_cells().len(), 15);
+    }
+
+    #[test]
+    fn test_is_complete() {
+        // Given
+        let mut board = Board::new_for_tests(2, 1);
+        let mut positions = vec![];
+        for row in 0..3 {
+            for col in 0..5 {
+                positions.push(board_position(row, col));
+            }
+        }
+        let last = positions.pop().unwrap();
+        board.place(0, &positions).unwrap();
+        assert!(!board.is_complete());
+
+        // When
+        board.place(1, &[last]).unwrap();
+
+        // Then
+        assert!(board.is_complete());
+    }
+
+    #[test]
+    fn test_board_from_pattern() {
+        // Given
+        let board = Board::from_pattern(2, 1, "XXXXX\nXX.X#\nXXXXX").unwrap();
+
+        // Then
+        assert_eq!((board.nb_rows, board.nb_cols), (3, 5));
+        assert_eq!(board.cell(1, 2), Cell::Hole);
+        assert_eq!(board.cell(1, 4), Cell::Blocked);
+        assert_eq!(board.free_cells().len(), 13);
+        assert_eq!(board.positions().len(), 13);
+        assert_eq!(board.blocked_positions(), vec![Vec3::new(300., 100., 0.)]);
+    }
+
+    #[test]
+    fn test_place_on_holes_and_blocked_cells() {
+        // Given
+        let mut board = Board::from_pattern(2, 1, "XXXXX\nXX.X#\nXXXXX").unwrap();
+
+        // Then
+        assert_eq!(
+            board.place(0, &[board_position(1, 1), board_position(1, 2)]),
+            Err(PlacementError::OutOfBoard)
+        );
+        assert_eq!(
+            board.place(0, &[board_position(1, 3), board_position(1, 4)]),
+            Err(PlacementError::Blocked)
+        );
+        assert_eq!(board.free_cells().len(), 13);
+    }
+
+    #[test]
+    fn test_is_complete_ignores_holes_and_blocked_cells() {
+        // Given
+        let mut board = Board::from_pattern(2, 1, "X.\nX#").unwrap();
+
+        // When
+        board
+            .place(0, &[board_position(0, 0), board_position(1, 0)])
+            .unwrap();
+
+        // Then
+        assert!(board.is_complete());
+    }
+
+    #[test]
+    fn test_remove_frees_cells() {
+        // Given
+        let mut board = Board::new_for_tests(2, 1);
+        board.place(0, &[board_position(0, 0)]).unwrap();
+        board.place(1, &[board_position(0, 1)]).unwrap();
+
+        // When
+        board.remove(0);
+
+        // Then
+        assert!(board.is_cell_free(0, 0));
+        assert_eq!(board.occupant_of(0, 1), Some(1));
+    }
+}
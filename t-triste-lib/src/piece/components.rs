@@ -0,0 +1,132 @@
+use bevy::{ecs::query::QueryData, prelude::*, sprite::Sprite};
+
+use crate::action::Device;
+use crate::piece::{cell_to_pixel, piece::Position, Piece, PieceView, SQUARE_WIDTH};
+use crate::state::InGame;
+
+/// Identifier of a piece: its index in the level. The board refers to pieces by their id.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PieceId(pub usize);
+
+/// Cell offsets of the piece in its current orientation, relative to its anchor
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct PieceShape(pub Vec<IVec2>);
+
+/// Grid cell the piece is anchored to
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridAnchor(pub IVec2);
+
+/// Grid cell the piece starts from, out of the board, where it goes back when returned
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrayAnchor(pub IVec2);
+
+/// Color used to render the piece
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct PieceColor(pub Color);
+
+/// Pixel position of the piece while the player drags it
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Drag(pub Option<Vec2>);
+
+/// Kind of device the piece is held with while the player moves it
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeldBy(pub Option<Device>);
+
+/// Child sprite rendering one square of a piece: the index of the square in its shape
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceSquare(pub usize);
+
+/// The components of a piece, which behaves as a `Piece` when queried mutably
+/// and as a `PieceView` otherwise
+#[derive(QueryData)]
+#[query_data(mutable)]
+pub struct PieceData {
+    pub id: &'static PieceId,
+    pub shape: &'static mut PieceShape,
+    pub anchor: &'static mut GridAnchor,
+    pub color: &'static PieceColor,
+    pub drag: &'static mut Drag,
+    pub held_by: &'static mut HeldBy,
+}
+
+/// Reads a piece from its components, whether they are queried mutably or not
+macro_rules! impl_piece_view {
+    ($item:ty) => {
+        impl PieceView for $item {
+            fn shape(&self) -> Vec<IVec2> {
+                self.shape.0.clone()
+            }
+
+            fn grid_anchor(&self) -> IVec2 {
+                self.anchor.0
+            }
+
+            fn drag_position(&self) -> Option<Vec2> {
+                self.drag.0
+            }
+
+            fn color(&self) -> Color {
+                self.color.0
+            }
+        }
+    };
+}
+
+impl_piece_view!(PieceDataItem<'_, '_>);
+impl_piece_view!(PieceDataReadOnlyItem<'_, '_>);
+
+impl Piece for PieceDataItem<'_, '_> {
+    fn shape_mut(&mut self) -> &mut [IVec2] {
+        &mut self.shape.0
+    }
+
+    fn set_grid_anchor(&mut self, anchor: IVec2) {
+        self.anchor.set_if_neq(GridAnchor(anchor));
+    }
+
+    fn set_drag_position(&mut self, position: Option<Vec2>) {
+        self.drag.set_if_neq(Drag(position));
+        // A dropped piece is no longer held
+        if position.is_none() {
+            self.held_by.set_if_neq(HeldBy(None));
+        }
+    }
+}
+
+/// Spawns a piece entity from its description, with one child sprite per square.
+/// The piece is despawned when the level is left.
+pub fn spawn_piece(commands: &mut Commands, id: usize, piece: &dyn PieceView) -> Entity {
+    let color = piece.color();
+    let shape = piece.shape();
+    commands
+        .spawn((
+            PieceId(id),
+            PieceShape(shape.clone()),
+            GridAnchor(piece.anchor()),
+            TrayAnchor(piece.anchor()),
+            PieceColor(color),
+            Drag::default(),
+            HeldBy::default(),
+            Transform::from_translation(cell_to_pixel(piece.anchor()).extend(1.)),
+            Visibility::default(),
+            DespawnOnExit(InGame),
+        ))
+        .with_children(|parent| {
+            for (square, offset) in shape.iter().enumerate() {
+                parent.spawn((
+                    Sprite {
+                        color,
+                        custom_size: Some(Vec2::new(
+                            (SQUARE_WIDTH - 1) as f32,
+                            (SQUARE_WIDTH - 1) as f32,
+                        )),
+                        ..default()
+                    },
+                    Transform::from_translation(cell_to_pixel(*offset).extend(0.)),
+                    Position,
+                    PieceSquare(square),
+                ));
+            }
+        })
+        .id()
+}
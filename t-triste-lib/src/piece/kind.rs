@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::piece::piece_builder::PieceBuilder;
+
+/// Every piece shape available to build a puzzle: the 12 free pentominoes
+/// and the 5 free tetrominoes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PieceKind {
+    PentominoF,
+    PentominoI,
+    PentominoL,
+    PentominoN,
+    PentominoP,
+    PentominoT,
+    PentominoU,
+    PentominoV,
+    PentominoW,
+    PentominoX,
+    PentominoY,
+    PentominoZ,
+    TetrominoI,
+    TetrominoO,
+    TetrominoT,
+    TetrominoL,
+    TetrominoS,
+}
+
+impl PieceKind {
+    /// The 12 free pentominoes, covering 60 cells
+    pub const PENTOMINOES: [PieceKind; 12] = [
+        PieceKind::PentominoF,
+        PieceKind::PentominoI,
+        PieceKind::PentominoL,
+        PieceKind::PentominoN,
+        PieceKind::PentominoP,
+        PieceKind::PentominoT,
+        PieceKind::PentominoU,
+        PieceKind::PentominoV,
+        PieceKind::PentominoW,
+        PieceKind::PentominoX,
+        PieceKind::PentominoY,
+        PieceKind::PentominoZ,
+    ];
+
+    /// The 5 free tetrominoes, covering 20 cells
+    pub const TETROMINOES: [PieceKind; 5] = [
+        PieceKind::TetrominoI,
+        PieceKind::TetrominoO,
+        PieceKind::TetrominoT,
+        PieceKind::TetrominoL,
+        PieceKind::TetrominoS,
+    ];
+
+    /// Returns every piece kind
+    pub fn all() -> impl Iterator<Item = PieceKind> {
+        Self::PENTOMINOES.into_iter().chain(Self::TETROMINOES)
+    }
+
+    /// Returns the stable name of the piece kind, used to reference it from outside the code
+    pub fn name(&self) -> &'static str {
+        match self {
+            PieceKind::PentominoF => "pentomino_f",
+            PieceKind::PentominoI => "pentomino_i",
+            PieceKind::PentominoL => "pentomino_l",
+            PieceKind::PentominoN => "pentomino_n",
+            PieceKind::PentominoP => "pentomino_p",
+            PieceKind::PentominoT => "pentomino_t",
+            PieceKind::PentominoU => "pentomino_u",
+            PieceKind::PentominoV => "pentomino_v",
+            PieceKind::PentominoW => "pentomino_w",
+            PieceKind::PentominoX => "pentomino_x",
+            PieceKind::PentominoY => "pentomino_y",
+            PieceKind::PentominoZ => "pentomino_z",
+            PieceKind::TetrominoI => "tetromino_i",
+            PieceKind::TetrominoO => "tetromino_o",
+            PieceKind::TetrominoT => "tetromino_t",
+            PieceKind::TetrominoL => "tetromino_l",
+            PieceKind::TetrominoS => "tetromino_s",
+        }
+    }
+
+    /// Finds a piece kind from its stable name
+    pub fn from_name(name: &str) -> Option<PieceKind> {
+        Self::all().find(|kind| kind.name() == name)
+    }
+
+    /// Returns the ASCII pattern of the piece kind, as parsed by `PieceBuilder::from_pattern`
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            PieceKind::PentominoF => ".XX\nXX.\n.X.",
+            PieceKind::PentominoI => "X\nX\nX\nX\nX",
+            PieceKind::PentominoL => "X.\nX.\nX.\nXX",
+            PieceKind::PentominoN => ".X\n.X\nXX\nX.",
+            PieceKind::PentominoP => "XX\nXX\nX.",
+            PieceKind::PentominoT => "XXX\n.X.\n.X.",
+            PieceKind::PentominoU => "X.X\nXXX",
+            PieceKind::PentominoV => "X..\nX..\nXXX",
+            PieceKind::PentominoW => "X..\nXX.\n.XX",
+            PieceKind::PentominoX => ".X.\nXXX\n.X.",
+            PieceKind::PentominoY => ".X\n.X\nXX\n.X",
+            PieceKind::PentominoZ => "XX.\n.X.\n.XX",
+            PieceKind::TetrominoI => "X\nX\nX\nX",
+            PieceKind::TetrominoO => "XX\nXX",
+            PieceKind::TetrominoT => "XXX\n.X.",
+            PieceKind::TetrominoL => "X.\nX.\nXX",
+            PieceKind::TetrominoS => ".XX\nXX.",
+        }
+    }
+
+    /// Returns the cell offsets of the piece kind, the bottom left corner being `(0, 0)`
+    pub fn shape(&self) -> Vec<IVec2> {
+        PieceBuilder::from_pattern(self.pattern()).expect("piece kind patterns are valid")
+    }
+
+    /// Returns the default color used to render the piece kind
+    pub fn color(&self) -> Color {
+        let index = Self::all()
+            .position(|kind| kind == *self)
+            .unwrap_or_default();
+        Color::hsl(index as f32 * 360. / 17., 0.8, 0.6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::piece::{orientations, AllowedTransforms};
+
+    /// Returns every rotation and reflection of a shape
+    fn all_orientations(kind: PieceKind) -> HashSet<Vec<IVec2>> {
+        orientations(&kind.shape(), AllowedTransforms::RotationsAndFlips)
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_piece_kinds_size() {
+        for kind in PieceKind::PENTOMINOES {
+            assert_eq!(kind.shape().len(), 5, "{}", kind.name());
+        }
+        for kind in PieceKind::TETROMINOES {
+            assert_eq!(kind.shape().len(), 4, "{}", kind.name());
+        }
+        let area: usize = PieceKind::PENTOMINOES
+            .iter()
+            .map(|kind| kind.shape().len())
+            .sum();
+        assert_eq!(area, 6 * 10);
+    }
+
+    #[test]
+    fn test_piece_kinds_are_distinct_free_polyominoes() {
+        let kinds: Vec<PieceKind> = PieceKind::all().collect();
+        for (i, first) in kinds.iter().enumerate() {
+            for second in kinds.iter().skip(i + 1) {
+                assert!(
+                    all_orientations(*first).is_disjoint(&all_orientations(*second)),
+                    "{} and {} are the same piece",
+                    first.name(),
+                    second.name()
+                );
+            }
+        }
+        // Symmetries of the classic pentominoes
+        assert_eq!(all_orientations(PieceKind::PentominoX).len(), 1);
+        assert_eq!(all_orientations(PieceKind::PentominoI).len(), 2);
+        assert_eq!(all_orientations(PieceKind::PentominoF).len(), 8);
+    }
+
+    #[test]
+    fn test_piece_kinds_are_connected() {
+        for kind in PieceKind::all() {
+            let shape = kind.shape();
+            let mut visited = vec![shape[0]];
+            let mut index = 0;
+            while index < visited.len() {
+                let cell = visited[index];
+                for neighbour in [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y] {
+                    let next = cell + neighbour;
+                    if shape.contains(&next) && !visited.contains(&next) {
+                        visited.push(next);
+                    }
+                }
+                index += 1;
+            }
+            assert_eq!(visited.len(), shape.len(), "{}", kind.name());
+        }
+    }
+
+    #[test]
+    fn test_piece_kind_names_are_stable() {
+        for kind in PieceKind::all() {
+            assert_eq!(PieceKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(
+            PieceKind::from_name("pentomino_w"),
+            Some(PieceKind::PentominoW)
+        );
+        assert_eq!(PieceKind::from_name("hexomino"), None);
+    }
+}
@@ -1,120 +1,376 @@
 pub mod board;
+pub mod kind;
+pub mod polyomino;
 
-mod corner;
-mod l;
+mod components;
 #[allow(clippy::module_inception)]
 mod piece;
 mod piece_builder;
-mod rectangle;
-mod square;
-mod z;
+
+pub use components::{
+    spawn_piece, Drag, GridAnchor, HeldBy, PieceColor, PieceData, PieceDataItem, PieceDataReadOnly,
+    PieceDataReadOnlyItem, PieceId, PieceShape, PieceSquare, TrayAnchor,
+};
+pub use piece::{
+    flip_shape, normalize_shape, orientations, rotate_shape, AllowedTransforms, FlipAxis, Piece,
+    PieceView, Rotation,
+};
+pub use piece_builder::{PatternError, PieceBuilder};
 
 extern crate t_triste_macro;
 
-use bevy::{math::vec3, prelude::*, sprite::Sprite};
+use bevy::prelude::*;
 
 use crate::{
+    action::{Action, Actions, Device},
     cursor::Cursor,
-    piece::{corner::Corner, l::L, rectangle::Rectangle, square::Square, z::Z},
+    history::{History, Turn},
+    piece::board::Board,
+    session::Session,
+    state::{AppState, InGame},
 };
-use piece::{Piece, Position};
 
 /// Width of each square in the puzzle pieces, measured in pixels
 pub const SQUARE_WIDTH: i32 = 50;
 
+/// Converts a grid cell into the pixel position of its centre
+pub fn cell_to_pixel(cell: IVec2) -> Vec2 {
+    (cell * SQUARE_WIDTH).as_vec2()
+}
+
+/// Returns the grid cell whose centre is the closest to a pixel position
+pub fn pixel_to_cell(position: Vec2) -> IVec2 {
+    (position / SQUARE_WIDTH as f32).round().as_ivec2()
+}
+
 /// Plugin that manages piece spawning, movement, rotation, and rendering
 pub struct PiecePlugin;
 
-/// Resource containing all game pieces currently in play
-pub struct GameState(pub Vec<Box<dyn Piece>>);
-
 impl Plugin for PiecePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_non_send_resource(GameState(vec![
-            Box::new(Rectangle::new(100, 100)),
-            Box::new(L::new(200, 300)),
-            Box::new(Z::new(400, 500)),
-            Box::new(Corner::new(100, 300)),
-            Box::new(Square::new(300, 100)),
-        ]))
-        .add_systems(PreUpdate, clear)
-        .add_systems(Update, (release_piece, click_piece, move_piece, draw_piece));
+        // Pieces are spawned when a level is loaded
+        app.add_systems(
+            Update,
+            (
+                (release_piece, click_piece, move_piece).run_if(in_state(AppState::Playing)),
+                (move_piece_sprites, reshape_piece_sprites).run_if(in_state(InGame)),
+            )
+                .chain(),
+        );
     }
 }
 
 // Systems
-fn clear(mut commands: Commands, query: Query<Entity, With<Position>>) {
-    for entity in query.iter() {
-        commands.entity(entity).despawn();
-    }
-}
-
-fn draw_piece(mut commands: Commands, mut game_state: NonSendMut<GameState>) {
-    for piece in game_state.0.iter_mut() {
-        let color = piece.color();
-        let positions = piece.positions();
-        for position in positions.iter() {
-            commands.spawn((
-                Sprite {
-                    color,
-                    custom_size: Some(Vec2::new(
-                        (SQUARE_WIDTH - 1) as f32,
-                        (SQUARE_WIDTH - 1) as f32,
-                    )),
-                    ..default()
-                },
-                Transform::from_translation(vec3(position.x, position.y, position.z)),
-                Position,
-            ));
+/// Pieces whose anchor or drag position changed since the last frame
+type MovedPiece = Or<(Changed<GridAnchor>, Changed<Drag>)>;
+
+/// Moves the piece entities whose anchor or drag position changed
+fn move_piece_sprites(mut query: Query<(&GridAnchor, &Drag, &mut Transform), MovedPiece>) {
+    for (anchor, drag, mut transform) in query.iter_mut() {
+        let origin = drag.0.unwrap_or_else(|| cell_to_pixel(anchor.0));
+        transform.translation = origin.extend(1.);
+    }
+}
+
+/// Moves the squares of the pieces that were rotated or flipped
+fn reshape_piece_sprites(
+    pieces: Query<(&PieceShape, &Children), Changed<PieceShape>>,
+    mut squares: Query<(&PieceSquare, &mut Transform)>,
+) {
+    for (shape, children) in pieces.iter() {
+        for child in children.iter() {
+            let Ok((square, mut transform)) = squares.get_mut(child) else {
+                continue;
+            };
+            if let Some(offset) = shape.0.get(square.0) {
+                let z = transform.translation.z;
+                transform.translation = cell_to_pixel(*offset).extend(z);
+            }
         }
     }
 }
 
-fn move_piece(cursor: Res<Cursor>, mut game_state: NonSendMut<GameState>) {
-    if cursor.is_pressed {
-        game_state
-            .0
+fn move_piece(cursor: Res<Cursor>, mut pieces: Query<PieceData>) {
+    if cursor.is_dragging {
+        pieces
             .iter_mut()
-            .filter(|piece| piece.is_moving())
-            .for_each(|piece| {
+            .filter(|piece| piece.held_by.0 == Some(Device::Mouse))
+            .for_each(|mut piece| {
                 piece.move_it(&cursor);
             })
     }
 }
 
+/// Returns the pieces sorted by id, the first one being picked when pieces overlap
+fn sorted<'w, 's>(pieces: &'s mut Query<'w, '_, PieceData>) -> Vec<PieceDataItem<'s, 's>> {
+    let mut pieces: Vec<PieceDataItem> = pieces.iter_mut().collect();
+    pieces.sort_by_key(|piece| *piece.id);
+    pieces
+}
+
 fn click_piece(
     cursor: Res<Cursor>,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut game_state: NonSendMut<GameState>,
+    actions: Res<Actions>,
+    mut pieces: Query<PieceData>,
+    mut board: Option<ResMut<Board>>,
+    mut history: ResMut<History>,
+    mut session: ResMut<Session>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        for piece in game_state.0.iter_mut() {
-            if piece.is_even_odd(cursor.current_pos) {
-                piece.set_moving(true);
+    if actions.just_pressed_by(Device::Mouse, Action::Pick) {
+        for mut piece in sorted(&mut pieces) {
+            if piece.is_even_odd(cursor.current_pos) && !piece.is_moving() {
+                pick_piece(&mut piece, Device::Mouse, &mut board, &mut history);
                 return;
             }
         }
     }
-    if mouse_button_input.just_pressed(MouseButton::Right) {
-        for piece in game_state.0.iter_mut() {
-            if piece.is_even_odd(cursor.current_pos) {
-                piece.rotate();
+    // While the mouse holds a piece, only that piece is turned
+    let holding = pieces
+        .iter()
+        .any(|piece| piece.held_by.0 == Some(Device::Mouse));
+    let targeted = |piece: &PieceDataItem| {
+        piece.is_even_odd(cursor.current_pos)
+            && can_handle(piece, Device::Mouse)
+            && (!holding || piece.held_by.0 == Some(Device::Mouse))
+    };
+    for (action, rotation) in [
+        (Action::RotateCw, Rotation::Clockwise),
+        (Action::RotateCcw, Rotation::CounterClockwise),
+    ] {
+        if actions.just_pressed_by(Device::Mouse, action) {
+            for mut piece in sorted(&mut pieces) {
+                if targeted(&piece) {
+                    rotate_piece(&mut piece, rotation, &mut board, &mut history, &mut session);
+                }
+            }
+        }
+    }
+    for (action, axis) in [
+        (Action::Flip, FlipAxis::Horizontal),
+        (Action::FlipVertical, FlipAxis::Vertical),
+    ] {
+        if actions.just_pressed_by(Device::Mouse, action) {
+            for mut piece in sorted(&mut pieces) {
+                if targeted(&piece) {
+                    flip_piece(&mut piece, axis, &mut board, &mut history, &mut session);
+                }
             }
         }
     }
 }
 
-fn release_piece(
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut game_state: NonSendMut<GameState>,
+/// Returns whether a device can handle a piece: a piece held with a device
+/// is left alone by the other ones
+pub(crate) fn can_handle(piece: &PieceDataItem, device: Device) -> bool {
+    piece.held_by.0.is_none_or(|holder| holder == device)
+}
+
+/// Starts moving a piece with a device, remembering where it was picked up from
+pub(crate) fn pick_piece(
+    piece: &mut PieceDataItem,
+    device: Device,
+    board: &mut Option<ResMut<Board>>,
+    history: &mut History,
+) {
+    let index = piece.id.0;
+    let placed = is_placed(board, index);
+    history.start_drag(index, piece.anchor(), placed);
+    piece.set_moving(true);
+    piece.held_by.0 = Some(device);
+    // A piece picked up from the board no longer fills its cells
+    if let Some(board) = board.as_mut().filter(|_| placed) {
+        board.remove(index);
+    }
+}
+
+/// Turns a piece a quarter and records it in the history
+pub(crate) fn rotate_piece(
+    piece: &mut PieceDataItem,
+    rotation: Rotation,
+    board: &mut Option<ResMut<Board>>,
+    history: &mut History,
+    session: &mut Session,
+) {
+    let index = piece.id.0;
+    let placed_before = is_placed(board, index);
+    session.rotations += 1;
+    match rotation {
+        Rotation::Clockwise => piece.rotate(),
+        Rotation::CounterClockwise => piece.rotate_counter_clockwise(),
+    }
+    let placed_after = reincrust(board, index, piece);
+    history.turn(index, Turn::Rotate(rotation), placed_before, placed_after);
+}
+
+/// Mirrors a piece and records it in the history
+pub(crate) fn flip_piece(
+    piece: &mut PieceDataItem,
+    axis: FlipAxis,
+    board: &mut Option<ResMut<Board>>,
+    history: &mut History,
+    session: &mut Session,
 ) {
-    if !mouse_button_input.just_released(MouseButton::Left) {
+    let index = piece.id.0;
+    let placed_before = is_placed(board, index);
+    session.flips += 1;
+    piece.flip(axis);
+    let placed_after = reincrust(board, index, piece);
+    history.turn(index, Turn::Flip(axis), placed_before, placed_after);
+}
+
+pub(crate) fn is_placed(board: &Option<ResMut<Board>>, index: usize) -> bool {
+    board.as_ref().is_some_and(|board| board.contains(index))
+}
+
+/// A rotated or flipped piece stays incrusted only if it still fits.
+/// A moving piece is incrusted when it is dropped.
+/// Returns whether the piece is incrusted.
+fn reincrust(board: &mut Option<ResMut<Board>>, index: usize, piece: &dyn Piece) -> bool {
+    let Some(board) = board.as_mut().filter(|_| !piece.is_moving()) else {
+        return false;
+    };
+    // The board is only changed when the piece leaves or enters it
+    let cells = piece.cells();
+    if board.contains(index) {
+        board.remove(index);
+    } else if board.cells_for(&cells).is_err() {
+        return false;
+    }
+    board.place(index, &cells).is_ok()
+}
+
+pub(crate) fn release_piece(actions: Res<Actions>, mut pieces: Query<PieceData>) {
+    if !actions.just_pressed_by(Device::Mouse, Action::Drop) {
         return;
     }
 
-    game_state
-        .0
+    pieces
         .iter_mut()
-        .filter(|piece| piece.is_moving())
-        .for_each(|piece| piece.set_moving(false));
+        .filter(|piece| piece.held_by.0 == Some(Device::Mouse))
+        .for_each(|mut piece| piece.set_moving(false));
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+
+    use super::*;
+    use crate::piece::{kind::PieceKind, polyomino::Polyomino};
+    use crate::test_utils::press;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .insert_state(AppState::Playing)
+            .add_computed_state::<InGame>()
+            .add_systems(Update, (move_piece_sprites, reshape_piece_sprites));
+        app
+    }
+
+    fn squares(app: &mut App) -> Vec<(Entity, Vec3)> {
+        app.world_mut()
+            .query::<(Entity, &PieceSquare, &GlobalTransform)>()
+            .iter(app.world())
+            .map(|(entity, _, transform)| (entity, transform.translation()))
+            .collect()
+    }
+
+    #[test]
+    fn test_piece_sprites_follow_the_piece() {
+        // Given
+        let mut app = app();
+        app.add_plugins(TransformPlugin);
+        let piece = Polyomino::new(PieceKind::TetrominoL, 0, 0);
+        let entity = spawn_piece(&mut app.world_mut().commands(), 0, &piece);
+        app.update();
+        let spawned = squares(&mut app);
+
+        // When
+        let mut piece = app.world_mut().entity_mut(entity);
+        piece.get_mut::<GridAnchor>().unwrap().0 = IVec2::new(1, 0);
+        rotate_shape(
+            &mut piece.get_mut::<PieceShape>().unwrap().0,
+            Rotation::Clockwise,
+        );
+        app.update();
+        app.update();
+
+        // Then
+        let moved = squares(&mut app);
+        assert_eq!(spawned.len(), 4);
+        assert_eq!(
+            spawned
+                .iter()
+                .map(|(entity, _)| *entity)
+                .collect::<Vec<_>>(),
+            moved.iter().map(|(entity, _)| *entity).collect::<Vec<_>>()
+        );
+        let mut cells: Vec<IVec2> = moved
+            .iter()
+            .map(|(_, translation)| pixel_to_cell(translation.truncate()))
+            .collect();
+        cells.sort_by_key(|cell| (cell.y, cell.x));
+        let mut expected = piece_cells(IVec2::new(1, 0), PieceKind::TetrominoL);
+        expected.sort_by_key(|cell| (cell.y, cell.x));
+        assert_eq!(cells, expected);
+    }
+
+    /// Cells of a piece kind rotated once, anchored on a cell
+    fn piece_cells(anchor: IVec2, kind: PieceKind) -> Vec<IVec2> {
+        let mut shape = kind.shape();
+        rotate_shape(&mut shape, Rotation::Clockwise);
+        shape.into_iter().map(|offset| anchor + offset).collect()
+    }
+
+    #[test]
+    fn test_pieces_behave_as_pieces_when_queried() {
+        // Given
+        let mut app = app();
+        let piece = Polyomino::new(PieceKind::TetrominoO, 2, 3);
+        spawn_piece(&mut app.world_mut().commands(), 7, &piece);
+        app.update();
+        let mut query = app.world_mut().query::<PieceData>();
+        let mut cursor = Cursor {
+            current_pos: Vec2::ZERO,
+            last_click_pos: cell_to_pixel(IVec2::new(2, 3)),
+            is_pressed: true,
+            is_dragging: true,
+        };
+
+        // When
+        let mut piece = query.single_mut(app.world_mut()).unwrap();
+        piece.set_moving(true);
+        cursor.current_pos = Vec2::new(4.2, 1.1) * SQUARE_WIDTH as f32;
+        piece.move_it(&cursor);
+        piece.rotate();
+        piece.set_moving(false);
+
+        // Then
+        assert_eq!(*piece.id, PieceId(7));
+        assert!(!piece.is_moving());
+        assert_eq!(piece.anchor(), IVec2::new(4, 1));
+        assert_eq!(piece.cells().len(), 4);
+    }
+
+    #[test]
+    fn test_turn_piece_while_the_button_is_held_over_empty_space() {
+        // Given
+        let mut app = crate::test_utils::app(AppState::Playing);
+        app.insert_resource(Cursor {
+            current_pos: Vec2::ZERO,
+            last_click_pos: Vec2::splat(1000.),
+            is_pressed: true,
+            is_dragging: true,
+        })
+        .add_plugins(PiecePlugin);
+        let piece = Polyomino::new(PieceKind::TetrominoL, 0, 0);
+        spawn_piece(&mut app.world_mut().commands(), 0, &piece);
+        app.update();
+
+        // When
+        press(&mut app, MouseButton::Right);
+
+        // Then
+        assert_eq!(app.world().resource::<Session>().rotations, 1);
+    }
 }
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 
 use crate::cursor::Cursor;
+use crate::piece::{cell_to_pixel, pixel_to_cell};
 
 use crate::SQUARE_WIDTH;
 
@@ -8,28 +9,160 @@ use crate::SQUARE_WIDTH;
 #[derive(Component)]
 pub struct Position;
 
-/// Trait representing a game piece that can be moved, rotated, and snapped to the board
-pub trait Piece {
-    /// Returns the current positions of all squares that make up this piece
-    fn positions(&self) -> Vec<Vec3>;
+/// Axis used to mirror a piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlipAxis {
+    /// Mirrors the piece left to right
+    Horizontal,
+    /// Mirrors the piece upside down
+    Vertical,
+}
+
+/// Direction of a quarter turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// Turns the piece to the right
+    Clockwise,
+    /// Turns the piece to the left
+    CounterClockwise,
+}
+
+/// Rotates cell offsets a quarter turn around the centre of their bounding box.
+///
+/// When the centre falls between two cells, the rotated cells are snapped half a cell
+/// up-right or down-left depending on the bounding box parity. This way four rotations,
+/// or a rotation followed by the opposite one, always bring the shape back in place.
+pub fn rotate_shape(shape: &mut [IVec2], rotation: Rotation) {
+    let (Some(min), Some(max)) = (
+        shape.iter().copied().reduce(IVec2::min),
+        shape.iter().copied().reduce(IVec2::max),
+    ) else {
+        return;
+    };
+    // Work with doubled coordinates so that the centre is an integer
+    let centre = min + max;
+    let snap = match (centre.x.rem_euclid(2), centre.y.rem_euclid(2)) {
+        (1, 0) => 1,
+        (0, 1) => -1,
+        _ => 0,
+    };
+    for offset in shape.iter_mut() {
+        let relative = *offset * 2 - centre;
+        let rotated = match rotation {
+            Rotation::Clockwise => IVec2::new(relative.y, -relative.x),
+            Rotation::CounterClockwise => relative.perp(),
+        };
+        *offset = (centre + rotated + IVec2::splat(snap)).div_euclid(IVec2::splat(2));
+    }
+}
+
+/// Mirrors cell offsets inside their bounding box
+pub fn flip_shape(shape: &mut [IVec2], axis: FlipAxis) {
+    let (Some(min), Some(max)) = (
+        shape.iter().copied().reduce(IVec2::min),
+        shape.iter().copied().reduce(IVec2::max),
+    ) else {
+        return;
+    };
+    for offset in shape.iter_mut() {
+        match axis {
+            FlipAxis::Horizontal => offset.x = min.x + max.x - offset.x,
+            FlipAxis::Vertical => offset.y = min.y + max.y - offset.y,
+        }
+    }
+}
+
+/// Transformations a piece is allowed to go through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AllowedTransforms {
+    /// The piece keeps its orientation
+    None,
+    /// The piece can be rotated
+    Rotations,
+    /// The piece can be rotated and flipped
+    #[default]
+    RotationsAndFlips,
+}
+
+/// Translates a shape so that its bounding box starts at `(0, 0)` and sorts its cells,
+/// so that two identical shapes are equal
+pub fn normalize_shape(shape: &[IVec2]) -> Vec<IVec2> {
+    let min = shape.iter().copied().reduce(IVec2::min).unwrap_or_default();
+    let mut cells: Vec<IVec2> = shape.iter().map(|cell| *cell - min).collect();
+    cells.sort_by_key(|cell| (cell.y, cell.x));
+    cells
+}
+
+/// Returns every distinct orientation of a shape, normalized
+pub fn orientations(shape: &[IVec2], allowed: AllowedTransforms) -> Vec<Vec<IVec2>> {
+    let mut shape = shape.to_vec();
+    let mut orientations = vec![normalize_shape(&shape)];
+    let (nb_rotations, nb_flips) = match allowed {
+        AllowedTransforms::None => (0, 1),
+        AllowedTransforms::Rotations => (4, 1),
+        AllowedTransforms::RotationsAndFlips => (4, 2),
+    };
+    for _ in 0..nb_flips {
+        for _ in 0..nb_rotations {
+            rotate_shape(&mut shape, Rotation::Clockwise);
+            let orientation = normalize_shape(&shape);
+            if !orientations.contains(&orientation) {
+                orientations.push(orientation);
+            }
+        }
+        flip_shape(&mut shape, FlipAxis::Horizontal);
+    }
+    orientations
+}
+
+/// Read access to a game piece, shared by the pieces described in the levels
+/// and by the pieces spawned as entities, even when they are queried read-only.
+///
+/// A piece is modeled as integer cell offsets relative to an anchor cell of the grid.
+/// Pixel positions are only derived from `SQUARE_WIDTH` when rendering.
+pub trait PieceView {
+    /// Returns the cell offsets that make up this piece, relative to its anchor
+    fn shape(&self) -> Vec<IVec2>;
+
+    /// Returns the grid cell the piece rests on, or was picked up from while it is moving
+    fn grid_anchor(&self) -> IVec2;
+
+    /// Returns the pixel position of the piece while the player drags it
+    fn drag_position(&self) -> Option<Vec2>;
 
     /// Returns the color used to render this piece
     fn color(&self) -> Color;
 
-    /// Rotates the piece 90 degrees clockwise around its first position
-    fn rotate(&mut self);
-
-    /// Snaps the piece positions to align with the board grid
-    fn snap(&mut self);
+    /// Returns the grid cell the piece is anchored to.
+    /// While the piece is moving, this is the cell it would snap to.
+    fn anchor(&self) -> IVec2 {
+        match self.drag_position() {
+            Some(position) => pixel_to_cell(position),
+            None => self.grid_anchor(),
+        }
+    }
 
-    /// Moves the piece to follow the cursor position
-    fn move_it(&mut self, cursor: &Res<Cursor>);
+    /// Returns the grid cells covered by this piece
+    fn cells(&self) -> Vec<IVec2> {
+        let anchor = self.anchor();
+        self.shape().iter().map(|offset| anchor + *offset).collect()
+    }
 
-    /// Sets whether this piece is currently being moved by the player
-    fn set_moving(&mut self, moving: bool);
+    /// Returns the pixel positions of all squares that make up this piece
+    fn positions(&self) -> Vec<Vec3> {
+        let origin = self
+            .drag_position()
+            .unwrap_or_else(|| cell_to_pixel(self.grid_anchor()));
+        self.shape()
+            .iter()
+            .map(|offset| (origin + cell_to_pixel(*offset)).extend(1.))
+            .collect()
+    }
 
     /// Returns true if this piece is currently being moved by the player
-    fn is_moving(&self) -> bool;
+    fn is_moving(&self) -> bool {
+        self.drag_position().is_some()
+    }
 
     /// Checks if a cursor position is within any of the piece's squares using
     /// point-in-rectangle collision detection
@@ -42,3 +175,246 @@ pub trait Piece {
         })
     }
 }
+
+/// Trait representing a game piece that can be moved, rotated, and snapped to the board
+pub trait Piece: PieceView {
+    /// Returns the cell offsets that make up this piece, to turn them in place
+    fn shape_mut(&mut self) -> &mut [IVec2];
+
+    /// Sets the grid cell the piece rests on
+    fn set_grid_anchor(&mut self, anchor: IVec2);
+
+    /// Sets the pixel position of the piece while the player drags it, `None` once dropped
+    fn set_drag_position(&mut self, position: Option<Vec2>);
+
+    /// Rotates the piece 90 degrees clockwise around its centre
+    fn rotate(&mut self) {
+        rotate_shape(self.shape_mut(), Rotation::Clockwise);
+    }
+
+    /// Rotates the piece 90 degrees counter-clockwise around its centre
+    fn rotate_counter_clockwise(&mut self) {
+        rotate_shape(self.shape_mut(), Rotation::CounterClockwise);
+    }
+
+    /// Mirrors the piece in place
+    fn flip(&mut self, axis: FlipAxis) {
+        flip_shape(self.shape_mut(), axis);
+    }
+
+    /// Snaps the piece anchor to the closest grid cell
+    fn snap(&mut self) {
+        let anchor = self.anchor();
+        self.set_grid_anchor(anchor);
+        if self.is_moving() {
+            self.set_drag_position(Some(cell_to_pixel(anchor)));
+        }
+    }
+
+    /// Puts the piece back on a grid cell, dropping it if it was moving
+    fn set_anchor(&mut self, anchor: IVec2) {
+        self.set_grid_anchor(anchor);
+        self.set_drag_position(None);
+    }
+
+    /// Moves the piece to follow the cursor position, keeping the offset between the
+    /// piece and the cursor when the piece was grabbed at `Cursor::last_click_pos`
+    fn move_it(&mut self, cursor: &Cursor) {
+        let origin = cell_to_pixel(self.grid_anchor());
+        self.set_drag_position(Some(origin + cursor.current_pos - cursor.last_click_pos));
+    }
+
+    /// Sets whether this piece is currently being moved by the player.
+    /// A released piece is snapped to the grid.
+    fn set_moving(&mut self, moving: bool) {
+        if moving {
+            self.set_drag_position(Some(cell_to_pixel(self.grid_anchor())));
+        } else {
+            self.snap();
+            self.set_drag_position(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::{kind::PieceKind, polyomino::Polyomino};
+
+    #[test]
+    fn test_rotate_is_exact() {
+        // Given
+        let mut piece = Polyomino::new(PieceKind::TetrominoL, 3, 4);
+        let cells = piece.cells();
+
+        // When
+        piece.rotate();
+        let rotated = piece.cells();
+        for _ in 0..3 {
+            piece.rotate();
+        }
+
+        // Then
+        // * * *
+        // *
+        assert_eq!(
+            rotated,
+            vec![
+                IVec2::new(3, 6),
+                IVec2::new(3, 5),
+                IVec2::new(4, 6),
+                IVec2::new(5, 6),
+            ]
+        );
+        assert_eq!(piece.cells(), cells);
+    }
+
+    #[test]
+    fn test_rotate_counter_clockwise_undoes_rotate() {
+        for mut piece in [
+            Box::new(Polyomino::new(PieceKind::TetrominoL, 3, 4)) as Box<dyn Piece>,
+            Box::new(Polyomino::new(PieceKind::TetrominoS, -2, 1)),
+        ] {
+            // Given
+            let cells = piece.cells();
+
+            // When
+            piece.rotate();
+            piece.rotate_counter_clockwise();
+
+            // Then
+            assert_eq!(piece.cells(), cells);
+        }
+    }
+
+    #[test]
+    fn test_rotate_keeps_piece_centre() {
+        // Given
+        // * * *
+        let mut shape = vec![IVec2::new(0, 0), IVec2::new(1, 0), IVec2::new(2, 0)];
+
+        // When
+        rotate_shape(&mut shape, Rotation::Clockwise);
+
+        // Then
+        assert_eq!(
+            shape,
+            vec![IVec2::new(1, 1), IVec2::new(1, 0), IVec2::new(1, -1)]
+        );
+    }
+
+    #[test]
+    fn test_flip_mirrors_piece() {
+        // Given
+        let mut piece = Polyomino::new(PieceKind::TetrominoL, 3, 4);
+        let cells = piece.cells();
+
+        // When
+        piece.flip(FlipAxis::Horizontal);
+        let mirrored = piece.cells();
+        piece.flip(FlipAxis::Vertical);
+
+        // Then
+        assert_eq!(
+            mirrored,
+            vec![
+                IVec2::new(4, 4),
+                IVec2::new(3, 4),
+                IVec2::new(4, 5),
+                IVec2::new(4, 6),
+            ]
+        );
+        // Flipping on both axes is a half turn
+        let mut rotated = Polyomino::new(PieceKind::TetrominoL, 3, 4);
+        rotated.rotate();
+        rotated.rotate();
+        assert_eq!(piece.cells(), rotated.cells());
+        assert_ne!(piece.cells(), cells);
+    }
+
+    #[test]
+    fn test_orientations() {
+        // Given
+        // *
+        // * *
+        let corner = vec![IVec2::new(0, 0), IVec2::new(1, 0), IVec2::new(0, 1)];
+        // * *
+        //   * *
+        let z = vec![
+            IVec2::new(1, 0),
+            IVec2::new(2, 0),
+            IVec2::new(0, 1),
+            IVec2::new(1, 1),
+        ];
+
+        // Then
+        assert_eq!(orientations(&corner, AllowedTransforms::None).len(), 1);
+        assert_eq!(orientations(&corner, AllowedTransforms::Rotations).len(), 4);
+        assert_eq!(
+            orientations(&corner, AllowedTransforms::RotationsAndFlips).len(),
+            4
+        );
+        assert_eq!(orientations(&z, AllowedTransforms::Rotations).len(), 2);
+        assert_eq!(
+            orientations(&z, AllowedTransforms::RotationsAndFlips).len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_release_snaps_to_closest_cell() {
+        // Given
+        let mut piece = Polyomino::new(PieceKind::TetrominoS, 0, 0);
+        piece.set_moving(true);
+        piece.move_it(&Cursor {
+            current_pos: Vec2::new(2. * SQUARE_WIDTH as f32 + 20., SQUARE_WIDTH as f32 - 20.),
+            last_click_pos: Vec2::ZERO,
+            is_pressed: true,
+            is_dragging: true,
+        });
+
+        // When
+        piece.set_moving(false);
+
+        // Then
+        assert!(!piece.is_moving());
+        assert_eq!(piece.anchor(), IVec2::new(2, 1));
+        assert_eq!(
+            piece.cells(),
+            vec![
+                IVec2::new(2, 1),
+                IVec2::new(3, 1),
+                IVec2::new(3, 2),
+                IVec2::new(4, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_move_keeps_grab_offset() {
+        // Given
+        let mut piece = Polyomino::new(PieceKind::TetrominoS, 1, 1);
+        let positions = piece.positions();
+        // Grab the piece by its last square
+        let grabbed = positions[3].truncate() + Vec2::new(10., -5.);
+        piece.set_moving(true);
+
+        // When
+        piece.move_it(&Cursor {
+            current_pos: grabbed + Vec2::new(3. * SQUARE_WIDTH as f32, 0.),
+            last_click_pos: grabbed,
+            is_pressed: true,
+            is_dragging: true,
+        });
+
+        // Then
+        assert_eq!(
+            piece.positions(),
+            positions
+                .iter()
+                .map(|position| *position + Vec3::new(3. * SQUARE_WIDTH as f32, 0., 0.))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(piece.anchor(), IVec2::new(4, 1));
+    }
+}
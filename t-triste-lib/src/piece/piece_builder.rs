@@ -1,28 +1,73 @@
 use bevy::prelude::*;
 
-use crate::piece::SQUARE_WIDTH;
+/// Reasons why an ASCII pattern cannot be turned into a piece shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternError {
+    /// The pattern does not contain any square
+    Empty,
+    /// The pattern contains a character that is neither a square (`X`), a hole (`.`)
+    /// nor a blocked board cell (`#`) when parsing a board
+    InvalidCharacter(char),
+}
 
 pub struct PieceBuilder {
     #[allow(dead_code)]
-    pub positions: Vec<Vec3>,
+    pub cells: Vec<IVec2>,
 }
 
 impl PieceBuilder {
-    pub fn new_horizontal_rectangle(
-        start_x: i32,
-        start_y: i32,
-        length: i32,
-        z_index: f32,
-    ) -> Vec<Vec3> {
-        let mut squares = vec![];
+    pub fn new_horizontal_rectangle(start_x: i32, start_y: i32, length: i32) -> Vec<IVec2> {
+        let mut cells = vec![];
         for i in 0..length {
-            squares.push(Vec3::new(
-                (start_x + i * SQUARE_WIDTH) as f32,
-                start_y as f32,
-                z_index,
-            ))
+            cells.push(IVec2::new(start_x + i, start_y))
+        }
+        cells
+    }
+
+    /// Parses an ASCII pattern into cell offsets.
+    ///
+    /// Each line is a row of the shape, from top to bottom. An `X` is a square and a `.` a hole.
+    /// The bottom left corner of the pattern is the `(0, 0)` offset:
+    /// ```text
+    /// X.
+    /// XX   =>   (1, 0) (0, 1) (1, 1) (0, 2)
+    /// .X
+    /// ```
+    pub fn from_pattern(pattern: &str) -> Result<Vec<IVec2>, PatternError> {
+        let cells: Vec<IVec2> = Self::parse_pattern(pattern, &['X'])?
+            .into_iter()
+            .map(|(cell, _)| cell)
+            .collect();
+        if cells.is_empty() {
+            return Err(PatternError::Empty);
         }
-        squares
+        Ok(cells)
+    }
+
+    /// Parses an ASCII pattern into the offsets of every character in `symbols`, along with
+    /// that character. A `.` is always a hole, any other character is invalid.
+    pub fn parse_pattern(
+        pattern: &str,
+        symbols: &[char],
+    ) -> Result<Vec<(IVec2, char)>, PatternError> {
+        let rows: Vec<&str> = pattern
+            .lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+        let mut cells = vec![];
+        for (y, row) in rows.iter().rev().enumerate() {
+            for (x, character) in row.chars().enumerate() {
+                match character {
+                    '.' => {}
+                    _ if symbols.contains(&character) => {
+                        cells.push((IVec2::new(x as i32, y as i32), character))
+                    }
+                    _ => return Err(PatternError::InvalidCharacter(character)),
+                }
+            }
+        }
+        Ok(cells)
     }
 }
 
@@ -32,7 +77,7 @@ mod tests {
 
     use super::*;
     use crate::piece::{
-        board::Board, corner::Corner, l::L, piece::Piece, piece::Position, square::Square, z::Z,
+        board::Board, kind::PieceKind, piece::PieceView, piece::Position, polyomino::Polyomino,
         SQUARE_WIDTH,
     };
 
@@ -47,7 +92,7 @@ mod tests {
         // *
         // *
         // * *
-        let piece = L::new(0, 0);
+        let piece = Polyomino::new(PieceKind::TetrominoL, 0, 0);
         let positions = piece.positions();
         let color = piece.color();
 
@@ -95,7 +140,7 @@ mod tests {
         // When
         // * *
         //   * *
-        let piece = Z::new(0, 0);
+        let piece = Polyomino::new(PieceKind::TetrominoS, 0, 0);
         let positions = piece.positions();
         let color = piece.color();
 
@@ -143,7 +188,7 @@ mod tests {
         // When
         // *
         // * *
-        let piece = Corner::new(0, 0);
+        let piece = Polyomino::from_pattern("X.\nXX", Color::WHITE, 0, 0).unwrap();
         let positions = piece.positions();
         let color = piece.color();
 
@@ -188,7 +233,7 @@ mod tests {
 
         // When
         // *
-        let piece = Square::new(0, 0);
+        let piece = Polyomino::from_pattern("X", Color::WHITE, 0, 0).unwrap();
         let positions = piece.positions();
         let color = piece.color();
 
@@ -217,6 +262,57 @@ mod tests {
         assert_eq!(results, vec![Vec3::new(0., 0., 1.),]);
     }
 
+    #[test]
+    fn test_from_pattern() {
+        // When
+        let cells = PieceBuilder::from_pattern("X.\nXX\n.X").unwrap();
+
+        // Then
+        assert_eq!(
+            cells,
+            vec![
+                IVec2::new(1, 0),
+                IVec2::new(0, 1),
+                IVec2::new(1, 1),
+                IVec2::new(0, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_pattern_ignores_indentation_and_blank_lines() {
+        // When
+        let cells = PieceBuilder::from_pattern(
+            "
+            XXX
+            .X.
+            ",
+        );
+
+        // Then
+        assert_eq!(
+            cells,
+            Ok(vec![
+                IVec2::new(1, 0),
+                IVec2::new(0, 1),
+                IVec2::new(1, 1),
+                IVec2::new(2, 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_from_pattern_errors() {
+        assert_eq!(
+            PieceBuilder::from_pattern("..\n.."),
+            Err(PatternError::Empty)
+        );
+        assert_eq!(
+            PieceBuilder::from_pattern("X*"),
+            Err(PatternError::InvalidCharacter('*'))
+        );
+    }
+
     #[test]
     fn test_board_positions() {
         // Given
@@ -224,8 +320,8 @@ mod tests {
         let mut command_queue = CommandQueue::default();
         let mut commands = Commands::new(&mut command_queue, &world);
 
-        let start_x = 100;
-        let start_y = 50;
+        let start_x = 2;
+        let start_y = 1;
 
         // When
         let board = Board::new_for_tests(start_x, start_y);
@@ -234,14 +330,29 @@ mod tests {
 
         // Then - Check that board has correct bounds
         let board = world.get_resource::<Board>().unwrap();
-        assert_eq!(board.min_x, start_x as f32);
-        assert_eq!(board.min_y, start_y as f32);
-        // Board is 3 rows x 5 cols, so max values should account for that
-        // Max is calculated as start + (count * SQUARE_WIDTH), not (count - 1) * SQUARE_WIDTH
-        assert_eq!(board.max_x, (start_x + 5 * SQUARE_WIDTH) as f32);
-        assert_eq!(board.max_y, (start_y + 3 * SQUARE_WIDTH) as f32);
+        assert_eq!(board.origin, IVec2::new(start_x, start_y));
+        assert_eq!(board.nb_rows, 3);
+        assert_eq!(board.nb_cols, 5);
 
         // Check that we have correct number of positions (3 rows * 5 cols = 15)
-        assert_eq!(board.positions.len(), 15);
+        let positions = board.positions();
+        assert_eq!(positions.len(), 15);
+        // Pixel positions are derived from the grid cells
+        assert_eq!(
+            positions.first(),
+            Some(&Vec3::new(
+                (start_x * SQUARE_WIDTH) as f32,
+                (start_y * SQUARE_WIDTH) as f32,
+                0.
+            ))
+        );
+        assert_eq!(
+            positions.last(),
+            Some(&Vec3::new(
+                ((start_x + 4) * SQUARE_WIDTH) as f32,
+                ((start_y + 2) * SQUARE_WIDTH) as f32,
+                0.
+            ))
+        );
     }
 }
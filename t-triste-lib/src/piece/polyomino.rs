@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use t_triste_macro::PieceBehavior;
+
+use crate::piece::{
+    kind::PieceKind,
+    piece_builder::{PatternError, PieceBuilder},
+};
+
+/// A piece made of any set of squares
+#[derive(PieceBehavior)]
+pub struct Polyomino {
+    shape: Vec<IVec2>,
+    anchor: IVec2,
+    color: Color,
+    drag_position: Option<Vec2>,
+}
+
+impl Polyomino {
+    pub fn new(kind: PieceKind, start_x: i32, start_y: i32) -> Self {
+        Polyomino {
+            shape: kind.shape(),
+            anchor: IVec2::new(start_x, start_y),
+            color: kind.color(),
+            drag_position: None,
+        }
+    }
+
+    /// Replaces the color of the piece
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Builds a piece from an ASCII pattern such as `"X.\nXX\n.X"`,
+    /// see `PieceBuilder::from_pattern` for the syntax
+    pub fn from_pattern(
+        pattern: &str,
+        color: Color,
+        start_x: i32,
+        start_y: i32,
+    ) -> Result<Self, PatternError> {
+        Ok(Polyomino {
+            shape: PieceBuilder::from_pattern(pattern)?,
+            anchor: IVec2::new(start_x, start_y),
+            color,
+            drag_position: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceView;
+
+    #[test]
+    fn test_polyomino_from_pattern() {
+        // Given
+        let color = Color::srgb(0.2, 0.4, 0.6);
+
+        // When
+        let piece = Polyomino::from_pattern("X.\nXX\n.X", color, 3, 2).unwrap();
+
+        // Then
+        assert_eq!(piece.color(), color);
+        assert_eq!(
+            piece.cells(),
+            vec![
+                IVec2::new(4, 2),
+                IVec2::new(3, 3),
+                IVec2::new(4, 3),
+                IVec2::new(3, 4),
+            ]
+        );
+    }
+}
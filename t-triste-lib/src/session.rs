@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::level::{CurrentLevel, Level};
+use crate::piece::{
+    board::{Board, BoardCompleted},
+    PieceId,
+};
+use crate::state::{AppState, InGame};
+
+/// Points of a completed level of difficulty 1, before penalties
+const BASE_SCORE: u32 = 1_000;
+
+/// Points lost for every second spent on the level
+const SECOND_PENALTY: u32 = 2;
+
+/// Points lost for every drop beyond one per piece
+const EXTRA_DROP_PENALTY: u32 = 20;
+
+/// Points lost for every rotation or flip
+const TRANSFORM_PENALTY: u32 = 5;
+
+/// Points lost for every undone action
+const UNDO_PENALTY: u32 = 10;
+
+/// Plugin that tracks the progress of the player on the current level
+/// and shows it in a HUD while the level is on screen
+pub struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Session>()
+            .add_systems(OnEnter(InGame), spawn_hud)
+            .add_systems(
+                Update,
+                (
+                    start_session.run_if(resource_added::<Board>),
+                    tick_session.run_if(in_state(AppState::Playing)),
+                    complete_session,
+                    update_hud.run_if(resource_changed::<Session>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Resource holding the progress of the player on the current level
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct Session {
+    /// Time spent on the level, stopped once the board is completed
+    pub elapsed: Duration,
+    /// Number of pieces dropped, not counting the ones released as they were picked up
+    pub drops: u32,
+    /// Number of quarter turns
+    pub rotations: u32,
+    /// Number of flips
+    pub flips: u32,
+    /// Number of undone actions
+    pub undos: u32,
+    /// Number of pieces of the level
+    pub nb_pieces: u32,
+    /// Difficulty of the level
+    pub difficulty: u8,
+    /// Score of the level, once the board is completed
+    pub score: Option<u32>,
+}
+
+impl Session {
+    pub fn new(nb_pieces: u32, difficulty: u8) -> Self {
+        Session {
+            nb_pieces,
+            difficulty,
+            ..default()
+        }
+    }
+
+    /// Returns true once the board is completed
+    pub fn is_complete(&self) -> bool {
+        self.score.is_some()
+    }
+
+    /// Computes the score of the level: harder levels are worth more, and points are lost
+    /// for the time spent and for every drop, rotation, flip or undo that was not needed
+    pub fn compute_score(&self) -> u32 {
+        let extra_drops = self.drops.saturating_sub(self.nb_pieces);
+        let penalty = SECOND_PENALTY * self.elapsed.as_secs() as u32
+            + EXTRA_DROP_PENALTY * extra_drops
+            + TRANSFORM_PENALTY * (self.rotations + self.flips)
+            + UNDO_PENALTY * self.undos;
+        (BASE_SCORE * self.difficulty.max(1) as u32).saturating_sub(penalty)
+    }
+}
+
+/// Marker component for the text of the HUD
+#[derive(Component)]
+struct HudText;
+
+// Systems
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: 20.,
+            ..default()
+        },
+        TextColor(Color::srgb(0.3, 0.2, 0.3)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            left: Val::Px(10.),
+            ..default()
+        },
+        HudText,
+        DespawnOnExit(InGame),
+    ));
+}
+
+fn start_session(
+    mut session: ResMut<Session>,
+    pieces: Query<(), With<PieceId>>,
+    current_level: Option<Res<CurrentLevel>>,
+    levels: Res<Assets<Level>>,
+) {
+    let difficulty = current_level
+        .and_then(|current_level| levels.get(&current_level.0))
+        .map_or(1, |level| level.difficulty);
+    *session = Session::new(pieces.iter().len() as u32, difficulty);
+}
+
+fn tick_session(mut session: ResMut<Session>, time: Res<Time>) {
+    if !session.is_complete() {
+        session.elapsed += time.delta();
+    }
+}
+
+fn complete_session(
+    mut session: ResMut<Session>,
+    mut board_completed: MessageReader<BoardCompleted>,
+) {
+    if board_completed.read().last().is_some() && !session.is_complete() {
+        session.score = Some(session.compute_score());
+    }
+}
+
+fn update_hud(session: Res<Session>, mut query: Query<&mut Text, With<HudText>>) {
+    let seconds = session.elapsed.as_secs();
+    let mut hud = format!(
+        "Time {:02}:{:02}   Drops {}   Rotations {}   Flips {}",
+        seconds / 60,
+        seconds % 60,
+        session.drops,
+        session.rotations,
+        session.flips
+    );
+    if let Some(score) = session.score {
+        hud += &format!("   Score {score}");
+    }
+    for mut text in query.iter_mut() {
+        text.0.clone_from(&hud);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perfect_score() {
+        // Given
+        let mut session = Session::new(5, 3);
+        session.drops = 5;
+
+        // Then
+        assert_eq!(session.compute_score(), 3 * BASE_SCORE);
+    }
+
+    #[test]
+    fn test_score_penalties() {
+        // Given
+        let session = Session {
+            elapsed: Duration::from_secs(30),
+            drops: 7,
+            rotations: 3,
+            flips: 1,
+            undos: 2,
+            ..Session::new(5, 1)
+        };
+
+        // Then
+        assert_eq!(session.compute_score(), 1_000 - 60 - 40 - 20 - 20);
+        // The score never goes below zero
+        let session = Session {
+            elapsed: Duration::from_secs(3600),
+            ..session
+        };
+        assert_eq!(session.compute_score(), 0);
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+
+use bevy::math::IVec2;
+
+use crate::piece::{board::Board, orientations, AllowedTransforms};
+
+/// A piece the solver can place on the board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolverPiece {
+    /// Cell offsets of the piece
+    pub shape: Vec<IVec2>,
+    /// Transformations the piece may go through to fit on the board
+    pub allowed: AllowedTransforms,
+}
+
+impl SolverPiece {
+    pub fn new(shape: Vec<IVec2>, allowed: AllowedTransforms) -> Self {
+        SolverPiece { shape, allowed }
+    }
+}
+
+/// A piece put on the board by the solver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    /// Index of the piece in the list given to the solver
+    pub piece: usize,
+    /// Orientation of the piece, as normalized cell offsets
+    pub shape: Vec<IVec2>,
+    /// Grid cell the orientation is anchored to
+    pub anchor: IVec2,
+}
+
+impl Placement {
+    /// Returns the grid cells covered by the piece
+    pub fn cells(&self) -> Vec<IVec2> {
+        self.shape
+            .iter()
+            .map(|offset| self.anchor + *offset)
+            .collect()
+    }
+}
+
+/// A tiling of the board: one placement per piece
+pub type Solution = Vec<Placement>;
+
+/// Figures about a run of the search, used to tell how hard a puzzle is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchStats {
+    /// Number of solutions found
+    pub solutions: usize,
+    /// Number of placements tried
+    pub nodes: usize,
+    /// Number of placements tried while they were the only candidate for a cell or a piece
+    pub forced_moves: usize,
+}
+
+/// Finds the ways to fill the free cells of a board with a set of pieces,
+/// every piece being used exactly once.
+///
+/// The problem is turned into an exact cover problem solved with Dancing Links:
+/// each free cell and each piece is a constraint, each possible placement of a piece
+/// covers the constraints of its cells and of the piece itself.
+pub struct Solver {
+    /// Every possible placement of every piece
+    placements: Vec<Placement>,
+    /// Constraints covered by each placement
+    rows: Vec<Vec<usize>>,
+    /// Number of constraints: free cells then pieces
+    nb_columns: usize,
+}
+
+impl Solver {
+    pub fn new(board: &Board, pieces: &[SolverPiece]) -> Self {
+        let free_cells: HashMap<IVec2, usize> = board
+            .free_cells()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (row, col))| (board.origin + IVec2::new(col as i32, row as i32), index))
+            .collect();
+        let nb_cells = free_cells.len();
+
+        let mut placements = vec![];
+        let mut rows = vec![];
+        for (index, piece) in pieces.iter().enumerate() {
+            // A piece without cells has no placement, so there is no solution
+            if piece.shape.is_empty() {
+                continue;
+            }
+            for shape in orientations(&piece.shape, piece.allowed) {
+                let mut anchors: Vec<IVec2> =
+                    free_cells.keys().map(|cell| *cell - shape[0]).collect();
+                // Keeps the placements order stable between runs
+                anchors.sort_by_key(|anchor| (anchor.y, anchor.x));
+                for anchor in anchors {
+                    let columns: Option<Vec<usize>> = shape
+                        .iter()
+                        .map(|offset| free_cells.get(&(anchor + *offset)).copied())
+                        .collect();
+                    if let Some(mut columns) = columns {
+                        columns.push(nb_cells + index);
+                        rows.push(columns);
+                        placements.push(Placement {
+                            piece: index,
+                            shape: shape.clone(),
+                            anchor,
+                        });
+                    }
+                }
+            }
+        }
+        Solver {
+            placements,
+            rows,
+            nb_columns: nb_cells + pieces.len(),
+        }
+    }
+
+    /// Returns the first solution found, if any
+    pub fn first(&self) -> Option<Solution> {
+        self.solve(Some(1)).pop()
+    }
+
+    /// Returns every solution, or only the first `limit` ones
+    pub fn solve(&self, limit: Option<usize>) -> Vec<Solution> {
+        let mut solutions = vec![];
+        self.search(|rows| {
+            solutions.push(
+                rows.iter()
+                    .map(|row| self.placements[*row].clone())
+                    .collect(),
+            );
+            limit.is_none_or(|limit| solutions.len() < limit)
+        });
+        solutions
+    }
+
+    /// Counts the solutions, stopping at `limit` when given
+    pub fn count(&self, limit: Option<usize>) -> usize {
+        let mut count = 0;
+        self.search(|_| {
+            count += 1;
+            limit.is_none_or(|limit| count < limit)
+        });
+        count
+    }
+
+    /// Explores the search tree, stopping after `limit` solutions when given
+    pub fn stats(&self, limit: Option<usize>) -> SearchStats {
+        let mut solutions = 0;
+        let mut stats = self.search(|_| {
+            solutions += 1;
+            limit.is_none_or(|limit| solutions < limit)
+        });
+        stats.solutions = solutions;
+        stats
+    }
+
+    /// Runs the search, calling `on_solution` with the rows of each solution
+    /// until it returns false
+    fn search(&self, mut on_solution: impl FnMut(&[usize]) -> bool) -> SearchStats {
+        let mut links = DancingLinks::new(self.nb_columns, &self.rows);
+        let mut partial = vec![];
+        let mut stats = SearchStats::default();
+        links.search(&mut partial, &mut stats, &mut on_solution);
+        stats
+    }
+}
+
+/// Sparse matrix of the exact cover problem, stored as circular doubly linked lists.
+/// Node 0 is the root, nodes `1..=nb_columns` are the column headers.
+struct DancingLinks {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    row: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DancingLinks {
+    fn new(nb_columns: usize, rows: &[Vec<usize>]) -> Self {
+        let nb_headers = nb_columns + 1;
+        let mut links = DancingLinks {
+            left: (0..nb_headers)
+                .map(|node| (node + nb_headers - 1) % nb_headers)
+                .collect(),
+            right: (0..nb_headers)
+                .map(|node| (node + 1) % nb_headers)
+                .collect(),
+            up: (0..nb_headers).collect(),
+            down: (0..nb_headers).collect(),
+            column: (0..nb_headers).collect(),
+            row: vec![usize::MAX; nb_headers],
+            size: vec![0; nb_headers],
+        };
+        for (index, columns) in rows.iter().enumerate() {
+            let first = links.left.len();
+            for (position, column) in columns.iter().enumerate() {
+                let header = column + 1;
+                let node = links.left.len();
+                links.left.push(if position == 0 { node } else { node - 1 });
+                links.right.push(first);
+                links.up.push(links.up[header]);
+                links.down.push(header);
+                links.column.push(header);
+                links.row.push(index);
+                let last = links.up[header];
+                links.down[last] = node;
+                links.up[header] = node;
+                links.size[header] += 1;
+                if position > 0 {
+                    links.right[node - 1] = node;
+                    links.left[first] = node;
+                }
+            }
+        }
+        links
+    }
+
+    fn cover(&mut self, header: usize) {
+        self.right[self.left[header]] = self.right[header];
+        self.left[self.right[header]] = self.left[header];
+        let mut node = self.down[header];
+        while node != header {
+            let mut other = self.right[node];
+            while other != node {
+                self.down[self.up[other]] = self.down[other];
+                self.up[self.down[other]] = self.up[other];
+                self.size[self.column[other]] -= 1;
+                other = self.right[other];
+            }
+            node = self.down[node];
+        }
+    }
+
+    fn uncover(&mut self, header: usize) {
+        let mut node = self.up[header];
+        while node != header {
+            let mut other = self.left[node];
+            while other != node {
+                self.size[self.column[other]] += 1;
+                self.down[self.up[other]] = other;
+                self.up[self.down[other]] = other;
+                other = self.left[other];
+            }
+            node = self.up[node];
+        }
+        self.right[self.left[header]] = header;
+        self.left[self.right[header]] = header;
+    }
+
+    /// Returns false once `on_solution` asked to stop
+    fn search(
+        &mut self,
+        partial: &mut Vec<usize>,
+        stats: &mut SearchStats,
+        on_solution: &mut impl FnMut(&[usize]) -> bool,
+    ) -> bool {
+        if self.right[0] == 0 {
+            return on_solution(partial);
+        }
+        // Branch on the constraint with the fewest candidates
+        let mut header = self.right[0];
+        let mut best = header;
+        while header != 0 {
+            if self.size[header] < self.size[best] {
+                best = header;
+            }
+            header = self.right[header];
+        }
+        if self.size[best] == 0 {
+            return true;
+        }
+
+        self.cover(best);
+        let mut node = self.down[best];
+        let mut keep_going = true;
+        while node != best && keep_going {
+            stats.nodes += 1;
+            if self.size[best] == 1 {
+                stats.forced_moves += 1;
+            }
+            partial.push(self.row[node]);
+            let mut other = self.right[node];
+            while other != node {
+                self.cover(self.column[other]);
+                other = self.right[other];
+            }
+            keep_going = self.search(partial, stats, on_solution);
+            let mut other = self.left[node];
+            while other != node {
+                self.uncover(self.column[other]);
+                other = self.left[other];
+            }
+            partial.pop();
+            node = self.down[node];
+        }
+        self.uncover(best);
+        keep_going
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::piece::{kind::PieceKind, PieceBuilder};
+
+    fn pieces(kinds: &[PieceKind]) -> Vec<SolverPiece> {
+        kinds
+            .iter()
+            .map(|kind| SolverPiece::new(kind.shape(), AllowedTransforms::RotationsAndFlips))
+            .collect()
+    }
+
+    fn assert_is_tiling(board: &Board, solution: &Solution) {
+        let cells: Vec<IVec2> = solution.iter().flat_map(Placement::cells).collect();
+        let unique: HashSet<IVec2> = cells.iter().copied().collect();
+        assert_eq!(cells.len(), unique.len());
+        assert_eq!(cells.len(), board.free_cells().len());
+        assert!(board.cells_for(&cells).is_ok());
+    }
+
+    #[test]
+    fn test_solve_first_level() {
+        // Given
+        let board = Board::new(6, 5, 3, 5);
+        let pieces: Vec<SolverPiece> = ["X\nX\nX", "X.\nX.\nXX", ".XX\nXX.", "X.\nXX", "X"]
+            .iter()
+            .map(|pattern| {
+                SolverPiece::new(
+                    PieceBuilder::from_pattern(pattern).unwrap(),
+                    AllowedTransforms::RotationsAndFlips,
+                )
+            })
+            .collect();
+
+        // When
+        let solution = Solver::new(&board, &pieces).first().unwrap();
+
+        // Then
+        assert_eq!(solution.len(), 5);
+        assert_is_tiling(&board, &solution);
+    }
+
+    #[test]
+    fn test_count_pentomino_3x20_solutions() {
+        // Given
+        let board = Board::new(0, 0, 3, 20);
+        let pieces = pieces(&PieceKind::PENTOMINOES);
+
+        // When
+        let count = Solver::new(&board, &pieces).count(None);
+
+        // Then
+        // 2 distinct solutions, times the 4 symmetries of the rectangle
+        assert_eq!(count, 8);
+    }
+
+    #[test]
+    fn test_tetrominoes_cannot_tile_a_rectangle() {
+        // Given
+        let board = Board::new(0, 0, 4, 5);
+        let pieces = pieces(&PieceKind::TETROMINOES);
+
+        // Then
+        assert_eq!(Solver::new(&board, &pieces).first(), None);
+    }
+
+    #[test]
+    fn test_empty_piece_has_no_solution() {
+        // Given
+        let board = Board::new(0, 0, 1, 1);
+        let pieces = vec![
+            SolverPiece::new(vec![IVec2::ZERO], AllowedTransforms::None),
+            SolverPiece::new(vec![], AllowedTransforms::RotationsAndFlips),
+        ];
+
+        // Then
+        assert_eq!(Solver::new(&board, &pieces).count(None), 0);
+    }
+
+    #[test]
+    fn test_search_stats() {
+        // Given
+        // X X X X
+        let board = Board::new(0, 0, 1, 4);
+        let pieces = vec![
+            SolverPiece::new(
+                PieceBuilder::from_pattern("XX").unwrap(),
+                AllowedTransforms::RotationsAndFlips,
+            ),
+            SolverPiece::new(
+                PieceBuilder::from_pattern("XX").unwrap(),
+                AllowedTransforms::RotationsAndFlips,
+            ),
+        ];
+
+        // When
+        let stats = Solver::new(&board, &pieces).stats(None);
+
+        // Then
+        // The first cell is covered by either piece, the rest of the board is forced
+        assert_eq!(
+            stats,
+            SearchStats {
+                solutions: 2,
+                nodes: 4,
+                forced_moves: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_solve_takes_filled_cells_into_account() {
+        // Given
+        // X X X
+        // X X X
+        let mut board = Board::new(0, 0, 2, 3);
+        board
+            .place(0, &[IVec2::new(0, 0), IVec2::new(0, 1), IVec2::new(1, 1)])
+            .unwrap();
+        let pieces = vec![SolverPiece::new(
+            PieceBuilder::from_pattern("XXX").unwrap(),
+            AllowedTransforms::None,
+        )];
+
+        // When
+        let solutions = Solver::new(&board, &pieces).solve(None);
+
+        // Then
+        assert!(solutions.is_empty());
+        let pieces = vec![SolverPiece::new(
+            PieceBuilder::from_pattern("X.\nXX").unwrap(),
+            AllowedTransforms::RotationsAndFlips,
+        )];
+        let solutions = Solver::new(&board, &pieces).solve(None);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(
+            solutions[0][0].cells(),
+            vec![IVec2::new(1, 0), IVec2::new(2, 0), IVec2::new(2, 1)]
+        );
+    }
+}
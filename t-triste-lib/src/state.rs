@@ -0,0 +1,388 @@
+use bevy::prelude::*;
+
+use crate::action::{Action, Actions};
+use crate::level::{CurrentLevel, Level, LevelList};
+use crate::piece::board::BoardCompleted;
+use crate::session::Session;
+
+/// Plugin that drives the flow of the game, from the main menu to the end of a level
+pub struct StatePlugin;
+
+impl Plugin for StatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .add_computed_state::<InGame>()
+            .init_resource::<HighlightedLevel>()
+            .add_systems(OnEnter(AppState::Menu), spawn_menu)
+            .add_systems(OnEnter(AppState::LevelSelect), spawn_level_select)
+            .add_systems(OnEnter(AppState::Paused), spawn_pause)
+            .add_systems(OnEnter(AppState::Completed), spawn_completed)
+            .add_systems(
+                Update,
+                (
+                    menu_input.run_if(in_state(AppState::Menu)),
+                    (update_level_select, level_select_input)
+                        .run_if(in_state(AppState::LevelSelect)),
+                    (pause_input, complete_level).run_if(in_state(AppState::Playing)),
+                    resume_input.run_if(in_state(AppState::Paused)),
+                    (update_completed, completed_input).run_if(in_state(AppState::Completed)),
+                ),
+            );
+    }
+}
+
+/// Screens of the game
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AppState {
+    /// Title screen
+    #[default]
+    Menu,
+    /// List of the levels to play
+    LevelSelect,
+    /// A level is being played
+    Playing,
+    /// A level is on screen but the player cannot move the pieces and the time is stopped
+    Paused,
+    /// The board of the level is complete
+    Completed,
+}
+
+/// State that exists while a level is on screen, whether it is played, paused or completed.
+/// The level is set up when entering it and torn down when leaving it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InGame;
+
+impl ComputedStates for InGame {
+    type SourceStates = AppState;
+
+    fn compute(state: AppState) -> Option<Self> {
+        match state {
+            AppState::Playing | AppState::Paused | AppState::Completed => Some(InGame),
+            AppState::Menu | AppState::LevelSelect => None,
+        }
+    }
+}
+
+/// Marker component for the text of the level select screen
+#[derive(Component)]
+struct LevelSelectText;
+
+/// Marker component for the text of the level completed screen
+#[derive(Component)]
+struct CompletedText;
+
+/// Resource holding the index of the level highlighted in the level select screen
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HighlightedLevel(pub usize);
+
+/// Spawns a centered text that is despawned when leaving `state`
+fn spawn_screen(commands: &mut Commands, state: AppState, text: String) -> Entity {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            DespawnOnExit(state),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(text),
+                TextFont {
+                    font_size: 28.,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.3, 0.2, 0.3)),
+                TextLayout::new_with_justify(Justify::Center),
+            ));
+        })
+        .id()
+}
+
+// Systems
+fn spawn_menu(mut commands: Commands) {
+    spawn_screen(
+        &mut commands,
+        AppState::Menu,
+        "T-Triste\n\nPress Enter to play".to_string(),
+    );
+}
+
+fn spawn_level_select(mut commands: Commands) {
+    let screen = spawn_screen(&mut commands, AppState::LevelSelect, String::new());
+    commands.entity(screen).insert(LevelSelectText);
+}
+
+fn spawn_pause(mut commands: Commands) {
+    spawn_screen(
+        &mut commands,
+        AppState::Paused,
+        "Paused\n\nEscape to resume, Q to quit the level".to_string(),
+    );
+}
+
+fn spawn_completed(mut commands: Commands) {
+    let screen = spawn_screen(&mut commands, AppState::Completed, String::new());
+    commands.entity(screen).insert(CompletedText);
+}
+
+fn menu_input(actions: Res<Actions>, mut next_state: ResMut<NextState<AppState>>) {
+    if actions.just_pressed(Action::Confirm) {
+        next_state.set(AppState::LevelSelect);
+    }
+}
+
+fn update_level_select(
+    level_list: Res<LevelList>,
+    levels: Res<Assets<Level>>,
+    highlighted: Res<HighlightedLevel>,
+    screens: Query<&Children, With<LevelSelectText>>,
+    mut texts: Query<&mut Text>,
+) {
+    let mut list = "Choose a level\n\n".to_string();
+    for (index, handle) in level_list.0.iter().enumerate() {
+        let name = levels
+            .get(handle)
+            .map_or("Loading...", |level| level.name.as_str());
+        let marker = if index == highlighted.0 { ">" } else { " " };
+        list += &format!("{marker} {}. {name}\n", index + 1);
+    }
+    list += "\nUp and Down to choose, Enter to play, Escape to go back";
+    for children in screens.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                if text.0 != list {
+                    text.0.clone_from(&list);
+                }
+            }
+        }
+    }
+}
+
+/// Shows the score of the session, which may be computed after the screen is spawned
+fn update_completed(
+    session: Res<Session>,
+    screens: Query<&Children, With<CompletedText>>,
+    mut texts: Query<&mut Text>,
+) {
+    let completed = format!(
+        "Level complete!\n\nScore {}\n\nPress Enter to continue",
+        session.score.unwrap_or_default()
+    );
+    for children in screens.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                if text.0 != completed {
+                    text.0.clone_from(&completed);
+                }
+            }
+        }
+    }
+}
+
+fn level_select_input(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    level_list: Res<LevelList>,
+    mut highlighted: ResMut<HighlightedLevel>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if actions.just_pressed(Action::Back) {
+        next_state.set(AppState::Menu);
+        return;
+    }
+    let nb_levels = level_list.0.len();
+    if nb_levels == 0 {
+        return;
+    }
+    // The highlight wraps around the list
+    if actions.just_pressed(Action::MenuUp) {
+        highlighted.0 = (highlighted.0 + nb_levels - 1) % nb_levels;
+    }
+    if actions.just_pressed(Action::MenuDown) {
+        highlighted.0 = (highlighted.0 + 1) % nb_levels;
+    }
+    if actions.just_pressed(Action::Confirm) {
+        if let Some(handle) = level_list.0.get(highlighted.0) {
+            commands.insert_resource(CurrentLevel(handle.clone()));
+            next_state.set(AppState::Playing);
+        }
+    }
+}
+
+fn pause_input(actions: Res<Actions>, mut next_state: ResMut<NextState<AppState>>) {
+    if actions.just_pressed(Action::Pause) {
+        next_state.set(AppState::Paused);
+    }
+}
+
+fn resume_input(actions: Res<Actions>, mut next_state: ResMut<NextState<AppState>>) {
+    if actions.just_pressed(Action::Pause) {
+        next_state.set(AppState::Playing);
+    } else if actions.just_pressed(Action::Quit) {
+        next_state.set(AppState::LevelSelect);
+    }
+}
+
+fn complete_level(
+    mut board_completed: MessageReader<BoardCompleted>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if board_completed.read().last().is_some() {
+        next_state.set(AppState::Completed);
+    }
+}
+
+fn completed_input(actions: Res<Actions>, mut next_state: ResMut<NextState<AppState>>) {
+    if actions.just_pressed(Action::Confirm) {
+        next_state.set(AppState::LevelSelect);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::gamepad::{Gamepad, GamepadButton};
+
+    use super::*;
+    use crate::test_utils::{press, press_button};
+
+    fn app(state: AppState) -> App {
+        let mut app = crate::test_utils::app(state);
+        app.init_resource::<Assets<Level>>()
+            .insert_resource(LevelList(Vec::new()))
+            .add_plugins(StatePlugin);
+        app
+    }
+
+    fn state(app: &App) -> AppState {
+        *app.world().resource::<State<AppState>>().get()
+    }
+
+    #[test]
+    fn test_in_game_states() {
+        assert_eq!(InGame::compute(AppState::Menu), None);
+        assert_eq!(InGame::compute(AppState::LevelSelect), None);
+        assert_eq!(InGame::compute(AppState::Playing), Some(InGame));
+        assert_eq!(InGame::compute(AppState::Paused), Some(InGame));
+        assert_eq!(InGame::compute(AppState::Completed), Some(InGame));
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        // Given
+        let mut app = app(AppState::Playing);
+        app.update();
+
+        // When
+        press(&mut app, KeyCode::Escape);
+        app.update();
+
+        // Then
+        assert_eq!(state(&app), AppState::Paused);
+        assert!(app.world().contains_resource::<State<InGame>>());
+
+        // When
+        press(&mut app, KeyCode::Escape);
+        app.update();
+
+        // Then
+        assert_eq!(state(&app), AppState::Playing);
+
+        // When
+        app.world_mut().write_message(BoardCompleted {
+            elapsed: default(),
+            moves: 1,
+        });
+        app.update();
+        app.update();
+
+        // Then
+        assert_eq!(state(&app), AppState::Completed);
+
+        // When
+        press(&mut app, KeyCode::Enter);
+        app.update();
+
+        // Then
+        assert_eq!(state(&app), AppState::LevelSelect);
+    }
+
+    #[test]
+    fn test_completed_screen_shows_the_score() {
+        // Given
+        let mut app = app(AppState::Completed);
+        app.update();
+
+        // When
+        // The score is computed once the screen is spawned
+        app.world_mut().resource_mut::<Session>().score = Some(850);
+        app.update();
+
+        // Then
+        let text = app
+            .world_mut()
+            .query::<&Text>()
+            .single(app.world())
+            .unwrap();
+        assert!(text.0.contains("Score 850"));
+    }
+
+    #[test]
+    fn test_choose_level_with_gamepad() {
+        // Given
+        let mut app = app(AppState::Menu);
+        let mut levels = Assets::<Level>::default();
+        let handles: Vec<Handle<Level>> = ["First", "Second"]
+            .iter()
+            .map(|name| {
+                levels.add(
+                    Level::from_ron(&format!(
+                        r#"(name: "{name}", author: "", difficulty: 1,
+                            board: (origin: (0, 0), pattern: "XX\nXX"),
+                            pieces: [(shape: Kind(tetromino_o), anchor: (0, 0))])"#
+                    ))
+                    .unwrap(),
+                )
+            })
+            .collect();
+        app.insert_resource(levels)
+            .insert_resource(LevelList(handles.clone()));
+        let gamepad = app.world_mut().spawn(Gamepad::default()).id();
+        app.update();
+
+        // When
+        press_button(&mut app, gamepad, GamepadButton::South);
+        app.update();
+        press_button(&mut app, gamepad, GamepadButton::DPadDown);
+
+        // Then
+        assert_eq!(state(&app), AppState::LevelSelect);
+        let text = app
+            .world_mut()
+            .query::<&Text>()
+            .single(app.world())
+            .unwrap()
+            .0
+            .clone();
+        assert!(text.contains("  1. First\n> 2. Second"));
+
+        // When
+        press_button(&mut app, gamepad, GamepadButton::South);
+        app.update();
+
+        // Then
+        assert_eq!(state(&app), AppState::Playing);
+        assert_eq!(app.world().resource::<CurrentLevel>().0, handles[1]);
+
+        // When
+        press_button(&mut app, gamepad, GamepadButton::Start);
+        press_button(&mut app, gamepad, GamepadButton::Select);
+        app.update();
+
+        // Then
+        assert_eq!(state(&app), AppState::LevelSelect);
+    }
+}
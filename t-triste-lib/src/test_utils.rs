@@ -0,0 +1,71 @@
+use std::hash::Hash;
+
+use bevy::input::gamepad::{Gamepad, GamepadAxis, GamepadButton};
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+
+use crate::action::ActionPlugin;
+use crate::history::History;
+use crate::piece::board::BoardPlugin;
+use crate::session::Session;
+use crate::state::{AppState, InGame};
+
+/// Builds a headless app in `state` that turns the inputs into actions,
+/// with the resources the pieces and the board need
+pub fn app(state: AppState) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>()
+        .init_resource::<History>()
+        .init_resource::<Session>()
+        .add_plugins((ActionPlugin, BoardPlugin))
+        .insert_state(state)
+        .add_computed_state::<InGame>();
+    app
+}
+
+/// Presses a key or a mouse button during one frame
+pub fn press<T: Copy + Eq + Hash + Send + Sync + 'static>(app: &mut App, input: T) {
+    app.world_mut()
+        .resource_mut::<ButtonInput<T>>()
+        .press(input);
+    app.update();
+    let mut button_input = app.world_mut().resource_mut::<ButtonInput<T>>();
+    button_input.release_all();
+    button_input.clear();
+}
+
+/// Presses a key or a mouse button during one frame and releases it during the next one
+pub fn click<T: Copy + Eq + Hash + Send + Sync + 'static>(app: &mut App, input: T) {
+    app.world_mut()
+        .resource_mut::<ButtonInput<T>>()
+        .press(input);
+    app.update();
+    app.world_mut()
+        .resource_mut::<ButtonInput<T>>()
+        .release(input);
+    app.update();
+    app.world_mut().resource_mut::<ButtonInput<T>>().clear();
+}
+
+/// Presses a gamepad button during one frame
+pub fn press_button(app: &mut App, gamepad: Entity, button: GamepadButton) {
+    let mut pad = app.world_mut().get_mut::<Gamepad>(gamepad).unwrap();
+    pad.digital_mut().press(button);
+    app.update();
+    let mut pad = app.world_mut().get_mut::<Gamepad>(gamepad).unwrap();
+    pad.digital_mut().release_all();
+    pad.digital_mut().clear();
+}
+
+/// Tilts the left stick during one frame
+pub fn tilt(app: &mut App, gamepad: Entity, direction: Vec2) {
+    let mut pad = app.world_mut().get_mut::<Gamepad>(gamepad).unwrap();
+    pad.analog_mut().set(GamepadAxis::LeftStickX, direction.x);
+    pad.analog_mut().set(GamepadAxis::LeftStickY, direction.y);
+    app.update();
+    let mut pad = app.world_mut().get_mut::<Gamepad>(gamepad).unwrap();
+    pad.analog_mut().set(GamepadAxis::LeftStickX, 0.);
+    pad.analog_mut().set(GamepadAxis::LeftStickY, 0.);
+}
@@ -8,80 +8,35 @@ pub fn derive_behavior_fn(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = &input.ident;
     let expanded = quote! {
-        impl crate::piece::Piece for #name {
-            fn positions(&self) -> Vec<Vec3> {
-                self.positions.clone()
+        impl crate::piece::PieceView for #name {
+            fn shape(&self) -> Vec<IVec2> {
+                self.shape.clone()
             }
 
-            fn color(&self) -> Color {
-                self.color.clone()
+            fn grid_anchor(&self) -> IVec2 {
+                self.anchor
             }
 
-            fn rotate(&mut self) {
-                let mut new_positions = vec![];
-
-                let s: f32 = std::f32::consts::FRAC_PI_2.sin();
-                let c: f32 = std::f32::consts::FRAC_PI_2.cos();
-
-                // We can unwrap as the first position exist
-                let first_pos = self.positions.first().unwrap();
-                let cx = first_pos.x;
-                let cy = first_pos.y;
-
-                for position in self.positions.iter() {
-                    let trans_x = position.x - cx;
-                    let trans_y = position.y - cy;
-
-                    let xnew = trans_x * c - trans_y * s;
-                    let ynew = trans_x * s + trans_y * c;
-
-                    new_positions.push(Vec3::new(xnew + cx, ynew + cy, position.z));
-                }
-
-                self.positions = new_positions;
+            fn drag_position(&self) -> Option<Vec2> {
+                self.drag_position
             }
 
-            fn move_it(&mut self, cursor: &Res<crate::cursor::Cursor>) {
-                let first_pos = self.positions.first_mut().unwrap();
-
-                let delta_x = -first_pos.x + cursor.current_pos.x;
-                let delta_y = -first_pos.y + cursor.current_pos.y;
-
-                first_pos.x = cursor.current_pos.x;
-                first_pos.y = cursor.current_pos.y;
-
-                for pos in self.positions.iter_mut().skip(1) {
-                    pos.x += delta_x;
-                    pos.y += delta_y;
-                }
+            fn color(&self) -> Color {
+                self.color
             }
+        }
 
-            fn snap(&mut self) {
-                for position in self.positions.iter_mut() {
-                    // Here we remove the modulo of a SQUARE height to map to a board position.
-                    let current_x_mod = (position.x as i32) % crate::SQUARE_WIDTH;
-                    let current_y_mod = (position.y as i32) % crate::SQUARE_WIDTH;
-                    let half_width = crate::SQUARE_WIDTH / 2;
-                    if current_x_mod > half_width {
-                        position.x = (position.x as i32 - current_x_mod + crate::SQUARE_WIDTH) as f32;
-                    } else {
-                        position.x = (position.x as i32 - current_x_mod) as f32;
-                    }
-
-                    if current_y_mod > half_width {
-                        position.y = (position.y as i32 - current_y_mod + crate::SQUARE_WIDTH) as f32;
-                    } else {
-                        position.y = (position.y as i32 - current_y_mod) as f32;
-                    }
-                }
+        impl crate::piece::Piece for #name {
+            fn shape_mut(&mut self) -> &mut [IVec2] {
+                &mut self.shape
             }
 
-            fn set_moving(&mut self, moving: bool) {
-                self.moving = moving;
+            fn set_grid_anchor(&mut self, anchor: IVec2) {
+                self.anchor = anchor;
             }
 
-            fn is_moving(&self) -> bool {
-                self.moving
+            fn set_drag_position(&mut self, position: Option<Vec2>) {
+                self.drag_position = position;
             }
         }
     };